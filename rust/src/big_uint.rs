@@ -0,0 +1,170 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{AddAssign, Mul};
+use std::str::FromStr;
+
+// Arbitrary precision unsigned integer as little endian base 2^32 limbs without trailing zeros
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BigUint {
+    limbs: Vec<u32>
+}
+
+impl BigUint {
+    pub fn zero() -> BigUint {
+        BigUint { limbs: vec![] }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    pub fn is_even(&self) -> bool {
+        self.limbs.first().is_none_or(|l| l % 2 == 0)
+    }
+
+    pub fn mul_add_small(&mut self, m: u32, a: u32) {
+        let mut carry = a as u64;
+        for limb in self.limbs.iter_mut() {
+            let v = *limb as u64 * m as u64 + carry;
+            *limb = v as u32;
+            carry = v >> 32;
+        }
+        if carry > 0 {
+            self.limbs.push(carry as u32);
+        }
+        self.normalize();
+    }
+
+    pub fn div_small(&mut self, d: u32) -> u32 {
+        let mut rem = 0u64;
+        for limb in self.limbs.iter_mut().rev() {
+            let v = (rem << 32) | *limb as u64;
+            *limb = (v / d as u64) as u32;
+            rem = v % d as u64;
+        }
+        self.normalize();
+        rem as u32
+    }
+
+    fn normalize(&mut self) {
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+    }
+}
+
+impl From<u64> for BigUint {
+    fn from(n: u64) -> BigUint {
+        let mut big = BigUint { limbs: vec![n as u32, (n >> 32) as u32] };
+        big.normalize();
+        big
+    }
+}
+
+impl AddAssign<&BigUint> for BigUint {
+    fn add_assign(&mut self, other: &BigUint) {
+        if self.limbs.len() < other.limbs.len() {
+            self.limbs.resize(other.limbs.len(), 0);
+        }
+        let mut carry = 0u64;
+        for (i, limb) in self.limbs.iter_mut().enumerate() {
+            let v = *limb as u64 + other.limbs.get(i).cloned().unwrap_or(0) as u64 + carry;
+            *limb = v as u32;
+            carry = v >> 32;
+        }
+        if carry > 0 {
+            self.limbs.push(carry as u32);
+        }
+    }
+}
+
+impl<'a> Mul<&'a BigUint> for &'a BigUint {
+    type Output = BigUint;
+
+    fn mul(self, other: &BigUint) -> BigUint {
+        let mut limbs = vec![0; self.limbs.len() + other.limbs.len()];
+        for (i, a) in self.limbs.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, b) in other.limbs.iter().enumerate() {
+                let v = limbs[i + j] as u64 + *a as u64 * *b as u64 + carry;
+                limbs[i + j] = v as u32;
+                carry = v >> 32;
+            }
+            limbs[i + other.limbs.len()] = carry as u32;
+        }
+        let mut product = BigUint { limbs };
+        product.normalize();
+        product
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &BigUint) -> Ordering {
+        self.limbs.len().cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &BigUint) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.limbs.is_empty() {
+            return write!(f, "0");
+        }
+
+        let mut n = self.clone();
+        let mut chunks = vec![];
+        while !n.limbs.is_empty() {
+            chunks.push(n.div_small(1_000_000_000));
+        }
+
+        write!(f, "{}", chunks.pop().unwrap())?;
+        for chunk in chunks.iter().rev() {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for BigUint {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err("empty number".into());
+        }
+
+        let mut n = BigUint::zero();
+        for c in s.chars() {
+            let digit = c.to_digit(10).ok_or_else(|| format!("invalid digit '{}'", c))?;
+            n.mul_add_small(10, digit);
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arithmetic() {
+        let mut n = BigUint::from(u64::MAX);
+        n += &BigUint::from(1);
+        assert_eq!(n.to_string(), "18446744073709551616");
+        n.mul_add_small(1_000_000_007, 3);
+        assert_eq!(n.to_string(), "18446744202836760131966861315");
+        assert_eq!(n.div_small(1_000_000_007), 3);
+        assert_eq!(n, "18446744073709551616".parse().unwrap());
+        assert_eq!((&n * &n).to_string(), "340282366920938463463374607431768211456");
+        assert_eq!(&n * &BigUint::zero(), BigUint::zero());
+        assert!(n > BigUint::from(u64::MAX) && BigUint::from(u64::MAX) > BigUint::from(1 << 32));
+        assert!(BigUint::zero().is_zero() && BigUint::from(0).is_zero());
+        assert!(BigUint::from(4).is_even() && !BigUint::from(5).is_even());
+        assert!("12a".parse::<BigUint>().is_err());
+    }
+}
@@ -7,6 +7,8 @@ use rayon::prelude::*;
 use std::u32;
 use std::cmp;
 use std::env;
+use std::error;
+use std::fmt;
 use std::collections::HashMap;
 use std::collections::VecDeque;
 use std::io::{self, BufReader};
@@ -18,7 +20,9 @@ use utils::*;
 #[derive(Debug)]
 struct Distance {
     loc_idxs: [usize; 2],
-    dist: u32
+    dist: u32,
+    // Only from the first location to the second, given as `A -> B = n`
+    one_way: bool
 }
 
 #[derive(Debug)]
@@ -30,7 +34,7 @@ struct Input {
 impl Input {
     fn parse(iter: impl IntoIterator<Item = String>) -> Input {
         lazy_static! {
-            static ref RE: Regex = Regex::new(r"^(.*) to (.*) = (\d*)$").unwrap();
+            static ref RE: Regex = Regex::new(r"^(.*?) (to|->) (.*) = (\d*)$").unwrap();
         }
 
         let mut locations: Vec<String> = vec![];
@@ -49,10 +53,11 @@ impl Input {
             for i in iter {
                 let caps = RE.captures(&i[..]).unwrap();
                 let l1 = loc_idx(caps.get(1).unwrap().as_str().into());
-                let l2 = loc_idx(caps.get(2).unwrap().as_str().into());
+                let l2 = loc_idx(caps.get(3).unwrap().as_str().into());
                 distances.push(Distance {
                     loc_idxs: [l1, l2],
-                    dist: caps.get(3).unwrap().as_str().parse::<u32>().unwrap()
+                    dist: caps.get(4).unwrap().as_str().parse::<u32>().unwrap(),
+                    one_way: caps.get(2).unwrap().as_str() == "->"
                 });
             }
         }
@@ -67,17 +72,19 @@ struct Route {
     dist: u32
 }
 
-fn find_min_max_routes(input: &Input) -> (u32, u32) {
+fn find_min_max_routes(input: &Input) -> Option<(u32, u32)> {
     // Build lookup for distances from one location to possible others
     let loc_dists = input.distances.iter()
         .fold(vec![HashMap::new(); input.locations.len()], |mut r: Vec<HashMap<usize, u32>>, dist| {
             r[dist.loc_idxs[0]].insert(dist.loc_idxs[1], dist.dist);
-            r[dist.loc_idxs[1]].insert(dist.loc_idxs[0], dist.dist);
+            if !dist.one_way {
+                r[dist.loc_idxs[1]].insert(dist.loc_idxs[0], dist.dist);
+            }
             r
         });
 
     // Run all possible routes from every location in parallel
-    (0..input.locations.len()).into_par_iter()
+    let (min_dist, max_dist) = (0..input.locations.len()).into_par_iter()
         .map(|loc_idx| {
             let mut min_dist = u32::MAX;
             let mut max_dist = u32::MIN;
@@ -118,20 +125,226 @@ fn find_min_max_routes(input: &Input) -> (u32, u32) {
         })
         .reduce(|| (u32::MAX, u32::MIN), |(min, max), (lmin, lmax)| {
             (cmp::min(min, lmin), cmp::max(max, lmax))
+        });
+
+    // No route visits every location
+    if min_dist == u32::MAX {
+        None
+    } else {
+        Some((min_dist, max_dist))
+    }
+}
+
+// A shortest or longest route through every location, by name in visiting order
+#[derive(Debug, PartialEq)]
+struct BestRoute {
+    locations: Vec<String>,
+    dist: u32
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Goal {
+    Shortest,
+    Longest
+}
+
+impl Goal {
+    fn better(self, dist: u32, than: u32) -> bool {
+        match self {
+            Goal::Shortest => dist < than,
+            Goal::Longest => dist > than
+        }
+    }
+}
+
+// Distance from every location to every other, None where there is no road between them
+fn distance_matrix(input: &Input) -> Vec<Vec<Option<u32>>> {
+    let n_locations = input.locations.len();
+    input.distances.iter()
+        .fold(vec![vec![None; n_locations]; n_locations], |mut m, dist| {
+            m[dist.loc_idxs[0]][dist.loc_idxs[1]] = Some(dist.dist);
+            if !dist.one_way {
+                m[dist.loc_idxs[1]][dist.loc_idxs[0]] = Some(dist.dist);
+            }
+            m
         })
 }
 
+// Where a route has to start and end, a round trip returns to where it started
+#[derive(Debug, Default)]
+struct Plan {
+    round_trip: bool,
+    start: Option<String>,
+    end: Option<String>
+}
+
+#[derive(Debug, PartialEq)]
+enum RouteError {
+    UnknownLocation(String),
+    RoundTripEnd(String),
+    NoRoute { locations: usize, round_trip: bool }
+}
+
+impl fmt::Display for RouteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RouteError::UnknownLocation(name) => write!(f, "unknown location {}", name),
+            RouteError::RoundTripEnd(name) => write!(f, "a round trip ends where it starts, not at {}", name),
+            RouteError::NoRoute { locations, round_trip: false } => write!(f, "no Hamiltonian path through the {} locations", locations),
+            RouteError::NoRoute { locations, round_trip: true } => write!(f, "no Hamiltonian cycle through the {} locations", locations)
+        }
+    }
+}
+
+impl error::Error for RouteError {}
+
+const UNREACHED: u32 = u32::MAX;
+
+// Held-Karp: the best distance of a route through the set of locations in a bitmask ending at
+// each of them is built from the routes through the same set minus the last location. The
+// route itself is traced back from the end by finding a predecessor that gives its distance,
+// which saves keeping a table of predecessors.
+fn held_karp(input: &Input, plan: &Plan, goal: Goal) -> Result<BestRoute, RouteError> {
+    let loc_idx = |name: &String| input.locations.iter().position(|l| l == name)
+        .ok_or_else(|| RouteError::UnknownLocation(name.clone()));
+    let start = plan.start.as_ref().map(loc_idx).transpose()?;
+    let end = plan.end.as_ref().map(loc_idx).transpose()?;
+    let no_route = RouteError::NoRoute { locations: input.locations.len(), round_trip: plan.round_trip };
+
+    let matrix = distance_matrix(input);
+    let n = input.locations.len();
+    if n == 0 {
+        return Err(no_route);
+    }
+    // Any location on a cycle can be where it starts
+    let start = if plan.round_trip { Some(start.or(end).unwrap_or(0)) } else { start };
+    if plan.round_trip && end.is_some_and(|end| Some(end) != start) {
+        return Err(RouteError::RoundTripEnd(plan.end.clone().unwrap()));
+    }
+
+    let all = (1usize << n) - 1;
+    let mut best = vec![UNREACHED; (all + 1) * n];
+    for loc_idx in (0..n).filter(|loc_idx| start.is_none_or(|start| start == *loc_idx)) {
+        best[(1 << loc_idx) * n + loc_idx] = 0;
+    }
+
+    for visited in 1..=all {
+        for last in 0..n {
+            let dist = best[visited * n + last];
+            if dist == UNREACHED {
+                continue;
+            }
+            for next in (0..n).filter(|next| visited & (1 << next) == 0) {
+                if let Some(d) = matrix[last][next] {
+                    let slot = &mut best[(visited | 1 << next) * n + next];
+                    if *slot == UNREACHED || goal.better(dist + d, *slot) {
+                        *slot = dist + d;
+                    }
+                }
+            }
+        }
+    }
+
+    // The distance of the whole route when it ends at last, going back to the start on a round trip
+    let total = |last: usize| -> Option<u32> {
+        let dist = best[all * n + last];
+        if dist == UNREACHED {
+            None
+        } else if !plan.round_trip {
+            if end.is_none_or(|end| end == last) { Some(dist) } else { None }
+        } else if n == 1 {
+            Some(dist)
+        } else {
+            matrix[last][start.unwrap()].map(|back| dist + back)
+        }
+    };
+    let mut last = (0..n)
+        .filter(|last| total(*last).is_some())
+        .fold(None, |found: Option<usize>, last| match found {
+            Some(found) if !goal.better(total(last).unwrap(), total(found).unwrap()) => Some(found),
+            _ => Some(last)
+        })
+        .ok_or(no_route)?;
+    let dist = total(last).unwrap();
+
+    let mut route = vec![last];
+    let mut visited = all;
+    while visited != 1 << last {
+        let before = visited & !(1 << last);
+        let prev = (0..n)
+            .find(|prev| {
+                let prev_dist = best[before * n + prev];
+                prev_dist != UNREACHED && matrix[*prev][last].is_some_and(|d| prev_dist + d == best[visited * n + last])
+            })
+            .unwrap();
+        route.push(prev);
+        visited = before;
+        last = prev;
+    }
+    route.reverse();
+    if plan.round_trip {
+        route.push(route[0]);
+    }
+
+    Ok(BestRoute { locations: route.iter().map(|loc_idx| input.locations[*loc_idx].clone()).collect(), dist })
+}
+
+fn format_route(route: Result<BestRoute, RouteError>) -> String {
+    match route {
+        Ok(route) => format!("{} ({})", route.dist, route.locations.join(" -> ")),
+        Err(e) => e.to_string()
+    }
+}
+
+struct Options {
+    input: String,
+    plan: Plan,
+    reference: bool
+}
+
+impl Options {
+    fn parse<I: Iterator<Item=String>>(mut args: I) -> Options {
+        let mut input = None;
+        let mut plan = Plan::default();
+        let mut reference = false;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--round-trip" => plan.round_trip = true,
+                "--from" => plan.start = Some(args.next().expect("No start location given")),
+                "--to" => plan.end = Some(args.next().expect("No end location given")),
+                "--reference" => reference = true,
+                _ => input = Some(arg)
+            }
+        }
+        Options { input: input.expect("No input file given"), plan, reference }
+    }
+}
+
 fn main() {
     measure(|| {
-        let input = input().expect("Input failed");
-        let (part1, part2) = find_min_max_routes(&input);
-        println!("Part1: {}", part1);
-        println!("Part2: {}", part2);
+        let options = Options::parse(env::args().skip(1));
+        let input = input(&options.input).expect("Input failed");
+        // The brute force only knows about open routes starting and ending anywhere
+        if options.reference {
+            match find_min_max_routes(&input) {
+                Some((part1, part2)) => {
+                    println!("Part1: {}", part1);
+                    println!("Part2: {}", part2);
+                },
+                None => println!("No route visits every location")
+            }
+        } else {
+            let plan = &options.plan;
+            let (shortest, longest) = rayon::join(|| held_karp(&input, plan, Goal::Shortest),
+                                                  || held_karp(&input, plan, Goal::Longest));
+            println!("Part1: {}", format_route(shortest));
+            println!("Part2: {}", format_route(longest));
+        }
     });
 }
 
-fn input() -> io::Result<Input> {
-    let f = File::open(env::args().skip(1).next().expect("No input file given"))?;
+fn input(path: &str) -> io::Result<Input> {
+    let f = File::open(path)?;
     let f = BufReader::new(f);
     Ok(Input::parse(f.lines().map(|l| l.unwrap())))
 }
@@ -151,11 +364,213 @@ mod tests {
 
     #[test]
     fn test_part1() {
-        assert_eq!(find_min_max_routes(&as_input(INPUT)).0, 605);
+        assert_eq!(find_min_max_routes(&as_input(INPUT)).unwrap().0, 605);
     }
 
     #[test]
     fn test_part2() {
-        assert_eq!(find_min_max_routes(&as_input(INPUT)).1, 982);
+        assert_eq!(find_min_max_routes(&as_input(INPUT)).unwrap().1, 982);
+    }
+
+    // Random distances between every two locations, or with one_way random one-way and two-way
+    // roads where some pairs of locations have none
+    fn random_roads(rng: &mut Lcg, n_locations: usize, one_way: bool) -> Input {
+        let mut lines = vec![];
+        for a in 0..n_locations {
+            for b in (a + 1)..n_locations {
+                match if one_way { rng.between(0, 3) } else { 3 } {
+                    0 => (),
+                    1 => lines.push(format!("L{} -> L{} = {}", a, b, rng.between(1, 100))),
+                    2 => lines.push(format!("L{} -> L{} = {}", b, a, rng.between(1, 100))),
+                    _ => lines.push(format!("L{} to L{} = {}", a, b, rng.between(1, 100)))
+                }
+            }
+        }
+        // Make sure every location is known even without roads
+        let mut input = Input::parse(lines);
+        for loc_idx in 0..n_locations {
+            if !input.locations.contains(&format!("L{}", loc_idx)) {
+                input.locations.push(format!("L{}", loc_idx));
+            }
+        }
+        input
+    }
+
+    fn route_dist(input: &Input, route: &BestRoute) -> u32 {
+        let matrix = distance_matrix(input);
+        let loc_idxs: Vec<usize> = route.locations.iter()
+            .map(|name| input.locations.iter().position(|l| l == name).unwrap())
+            .collect();
+        loc_idxs.windows(2).map(|w| if w[0] == w[1] { 0 } else { matrix[w[0]][w[1]].unwrap() }).sum()
+    }
+
+    #[test]
+    fn test_held_karp() {
+        let input = as_input(INPUT);
+        let shortest = held_karp(&input, &Plan::default(), Goal::Shortest).unwrap();
+        assert_eq!(shortest.dist, 605);
+        assert!(shortest.locations == vec!["London", "Dublin", "Belfast"] ||
+                shortest.locations == vec!["Belfast", "Dublin", "London"]);
+        let longest = held_karp(&input, &Plan::default(), Goal::Longest).unwrap();
+        assert_eq!(longest.dist, 982);
+        assert!(longest.locations == vec!["Dublin", "London", "Belfast"] ||
+                longest.locations == vec!["Belfast", "London", "Dublin"]);
+    }
+
+    #[test]
+    fn test_held_karp_matches_brute_force() {
+        let mut rng = Lcg::new(9);
+        for n_locations in 2..9 {
+            for _ in 0..5 {
+                let input = random_roads(&mut rng, n_locations, false);
+                let shortest = held_karp(&input, &Plan::default(), Goal::Shortest).unwrap();
+                let longest = held_karp(&input, &Plan::default(), Goal::Longest).unwrap();
+                assert_eq!(Some((shortest.dist, longest.dist)), find_min_max_routes(&input));
+                for route in [shortest, longest] {
+                    assert_eq!(route.locations.len(), n_locations);
+                    assert_eq!(route_dist(&input, &route), route.dist);
+                }
+            }
+        }
+    }
+
+    // Tries every order of the locations
+    fn brute_force(input: &Input, plan: &Plan, goal: Goal) -> Option<u32> {
+        fn visit(matrix: &[Vec<Option<u32>>], order: &mut Vec<usize>, dist: u32, plan: &Plan, names: &[String], goal: Goal, best: &mut Option<u32>) {
+            let n = matrix.len();
+            if order.len() == n {
+                let name = |loc_idx: usize| Some(&names[loc_idx]);
+                let dist = if plan.round_trip {
+                    if n == 1 { Some(dist) } else { matrix[order[n - 1]][order[0]].map(|back| dist + back) }
+                } else if plan.end.is_none() || plan.end.as_ref() == name(order[n - 1]) {
+                    Some(dist)
+                } else {
+                    None
+                };
+                if let Some(dist) = dist {
+                    if best.is_none_or(|best| goal.better(dist, best)) {
+                        *best = Some(dist);
+                    }
+                }
+                return;
+            }
+            for next in 0..n {
+                if order.contains(&next) {
+                    continue;
+                }
+                let step = match order.last() {
+                    Some(last) => matrix[*last][next],
+                    None if plan.start.is_none() || plan.start.as_ref() == Some(&names[next]) => Some(0),
+                    None => None
+                };
+                if let Some(step) = step {
+                    order.push(next);
+                    visit(matrix, order, dist + step, plan, names, goal, best);
+                    order.pop();
+                }
+            }
+        }
+
+        let mut best = None;
+        visit(&distance_matrix(input), &mut vec![], 0, plan, &input.locations, goal, &mut best);
+        best
+    }
+
+    #[test]
+    fn test_plans_match_brute_force() {
+        let mut rng = Lcg::new(42);
+        for n_locations in 1..7 {
+            for _ in 0..20 {
+                let input = random_roads(&mut rng, n_locations, true);
+                let mut plans = vec![
+                    Plan::default(),
+                    Plan { round_trip: true, start: None, end: None },
+                    Plan { round_trip: false, start: Some("L0".to_string()), end: None },
+                    Plan { round_trip: false, start: None, end: Some("L0".to_string()) }
+                ];
+                if n_locations > 1 {
+                    plans.push(Plan { round_trip: false, start: Some("L0".to_string()), end: Some("L1".to_string()) });
+                    plans.push(Plan { round_trip: true, start: Some("L1".to_string()), end: None });
+                }
+                for plan in plans.iter() {
+                    for goal in [Goal::Shortest, Goal::Longest] {
+                        let route = held_karp(&input, plan, goal);
+                        assert_eq!(route.as_ref().map(|r| r.dist).ok(), brute_force(&input, plan, goal),
+                                   "{:?} {:?} {:?}", input, plan, goal);
+                        if let Ok(route) = route {
+                            assert_eq!(route_dist(&input, &route), route.dist);
+                            assert_eq!(route.locations.len(), n_locations + if plan.round_trip { 1 } else { 0 });
+                            if let Some(start) = plan.start.as_ref() {
+                                assert_eq!(&route.locations[0], start);
+                            }
+                        }
+                    }
+                }
+                // The enumeration never finishes a route of a single location
+                if n_locations > 1 {
+                    assert_eq!(find_min_max_routes(&input).map(|(min, _)| min), brute_force(&input, &plans[0], Goal::Shortest));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_plans() {
+        let input = as_input(INPUT);
+        let round_trip = Plan { round_trip: true, start: Some("Dublin".to_string()), end: None };
+        let route = held_karp(&input, &round_trip, Goal::Shortest).unwrap();
+        assert_eq!(route.dist, 1123);
+        assert_eq!(route.locations.first(), route.locations.last());
+        assert_eq!(route.locations[0], "Dublin");
+
+        let from_dublin = Plan { round_trip: false, start: Some("Dublin".to_string()), end: None };
+        assert_eq!(held_karp(&input, &from_dublin, Goal::Shortest).unwrap().locations, vec!["Dublin", "Belfast", "London"]);
+        let to_london = Plan { round_trip: false, start: None, end: Some("London".to_string()) };
+        assert_eq!(held_karp(&input, &to_london, Goal::Longest).unwrap().locations, vec!["Dublin", "Belfast", "London"]);
+    }
+
+    #[test]
+    fn test_one_way() {
+        let input = as_input(
+           "London -> Dublin = 464
+            Belfast -> London = 518
+            Dublin to Belfast = 141");
+        let route = held_karp(&input, &Plan::default(), Goal::Shortest).unwrap();
+        assert_eq!(route.locations, vec!["London", "Dublin", "Belfast"]);
+        assert_eq!(route.dist, 605);
+        assert_eq!(find_min_max_routes(&input), Some((605, 982)));
+
+        let round_trip = Plan { round_trip: true, start: None, end: None };
+        assert_eq!(held_karp(&input, &round_trip, Goal::Shortest).unwrap().dist, 1123);
+    }
+
+    #[test]
+    fn test_route_errors() {
+        let input = as_input(
+           "London -> Dublin = 464
+            Belfast -> Dublin = 518
+            Paris to Rome = 100");
+        assert_eq!(find_min_max_routes(&input), None);
+        let error = held_karp(&input, &Plan::default(), Goal::Shortest).unwrap_err();
+        assert_eq!(error, RouteError::NoRoute { locations: 5, round_trip: false });
+        assert_eq!(error.to_string(), "no Hamiltonian path through the 5 locations");
+
+        let input = as_input(INPUT);
+        let plan = Plan { round_trip: false, start: Some("Oslo".to_string()), end: None };
+        assert_eq!(held_karp(&input, &plan, Goal::Shortest).unwrap_err(), RouteError::UnknownLocation("Oslo".to_string()));
+        let plan = Plan { round_trip: true, start: Some("London".to_string()), end: Some("Dublin".to_string()) };
+        assert_eq!(held_karp(&input, &plan, Goal::Shortest).unwrap_err().to_string(), "a round trip ends where it starts, not at Dublin");
+    }
+
+    #[test]
+    fn test_held_karp_many_locations() {
+        let input = random_roads(&mut Lcg::new(2015), 15, false);
+        let route = held_karp(&input, &Plan::default(), Goal::Shortest).unwrap();
+        let mut locations = route.locations.clone();
+        locations.sort();
+        let mut expected = input.locations.clone();
+        expected.sort();
+        assert_eq!(locations, expected);
+        assert_eq!(route_dist(&input, &route), route.dist);
     }
 }
\ No newline at end of file
@@ -3,6 +3,9 @@ extern crate regex;
 extern crate utils;
 
 use std::env;
+use std::error;
+use std::fmt;
+use std::collections::HashMap;
 use std::io::{self, BufReader};
 use std::io::prelude::*;
 use std::fs::File;
@@ -168,25 +171,422 @@ fn max_happiness_change(input: &Input) -> i32 {
     max_happiness_change
 }
 
-fn part1(input: &Input) -> i32 {
-    max_happiness_change(input)
+// Two neighbours at the table and how much happier each of them gets from sitting next to the other
+#[derive(Debug, PartialEq)]
+struct Neighbours {
+    persons: [String; 2],
+    happiness_changes: [i32; 2]
 }
 
-fn part2(input: &mut Input) -> i32 {
+// Everyone around the table in seating order, the last person sits next to the first
+#[derive(Debug, PartialEq)]
+struct Arrangement {
+    seats: Vec<String>,
+    neighbours: Vec<Neighbours>,
+    happiness_change: i32
+}
+
+impl Arrangement {
+    fn new(input: &Input, seats: &[usize]) -> Arrangement {
+        let neighbours: Vec<_> = if seats.len() < 2 {
+            vec![]
+        } else {
+            (0..seats.len())
+                .map(|i| {
+                    let (a, b) = (seats[i], seats[(i + 1) % seats.len()]);
+                    Neighbours {
+                        persons: [input.persons[a].clone(), input.persons[b].clone()],
+                        happiness_changes: [input.happiness_change_next_to[a][b], input.happiness_change_next_to[b][a]]
+                    }
+                })
+                .collect()
+        };
+        Arrangement {
+            seats: seats.iter().map(|pidx| input.persons[*pidx].clone()).collect(),
+            happiness_change: neighbours.iter().map(|n| n.happiness_changes[0] + n.happiness_changes[1]).sum(),
+            neighbours
+        }
+    }
+}
+
+// A hard requirement on where a person sits, tables are numbered from 1
+#[derive(Debug, Clone, PartialEq)]
+enum Constraint {
+    NextTo(String, String),
+    NotNextTo(String, String),
+    AtTable(String, usize)
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Constraint::NextTo(a, b) => write!(f, "{} next to {}", a, b),
+            Constraint::NotNextTo(a, b) => write!(f, "{} not next to {}", a, b),
+            Constraint::AtTable(a, table) => write!(f, "{} at table {}", a, table)
+        }
+    }
+}
+
+// The sizes of the tables to seat everyone at and the constraints on the seating, without any
+// sizes everyone sits at the same table
+#[derive(Debug, Default)]
+struct Tables {
+    sizes: Vec<usize>,
+    constraints: Vec<Constraint>
+}
+
+#[derive(Debug, PartialEq)]
+enum SeatingError {
+    UnknownPerson(String),
+    UnknownTable(usize),
+    EmptyTable(usize),
+    SeatCount { seats: usize, persons: usize },
+    // A minimal set of the constraints that can't all hold together, where leaving out any one
+    // of them would let the rest hold. There may be smaller sets that conflict.
+    Infeasible(Vec<Constraint>)
+}
+
+impl fmt::Display for SeatingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SeatingError::UnknownPerson(name) => write!(f, "unknown person {}", name),
+            SeatingError::UnknownTable(table) => write!(f, "there is no table {}", table),
+            SeatingError::EmptyTable(table) => write!(f, "table {} has no seats", table),
+            SeatingError::SeatCount { seats, persons } => write!(f, "{} seats for {} persons", seats, persons),
+            SeatingError::Infeasible(constraints) => {
+                let constraints: Vec<_> = constraints.iter().map(|c| c.to_string()).collect();
+                write!(f, "these constraints can't all hold: {}", constraints.join(", "))
+            }
+        }
+    }
+}
+
+impl error::Error for SeatingError {}
+
+// The constraints by person index
+struct Rules {
+    next_to: Vec<Vec<bool>>,
+    apart: Vec<Vec<bool>>,
+    table: Vec<Option<usize>>
+}
+
+impl Rules {
+    fn none(n_persons: usize) -> Rules {
+        Rules { next_to: vec![vec![false; n_persons]; n_persons], apart: vec![vec![false; n_persons]; n_persons], table: vec![None; n_persons] }
+    }
+
+    fn new(input: &Input, n_tables: usize, constraints: &[Constraint]) -> Result<Rules, SeatingError> {
+        let pidx = |name: &String| input.persons.iter().position(|p| p == name)
+            .ok_or_else(|| SeatingError::UnknownPerson(name.clone()));
+        let mut rules = Rules::none(input.persons.len());
+        for constraint in constraints {
+            match constraint {
+                Constraint::NextTo(a, b) => {
+                    let (a, b) = (pidx(a)?, pidx(b)?);
+                    // Nobody sits next to themselves
+                    if a == b {
+                        return Err(SeatingError::Infeasible(vec![constraint.clone()]));
+                    }
+                    rules.next_to[a][b] = true;
+                    rules.next_to[b][a] = true;
+                },
+                Constraint::NotNextTo(a, b) => {
+                    let (a, b) = (pidx(a)?, pidx(b)?);
+                    rules.apart[a][b] = true;
+                    rules.apart[b][a] = true;
+                },
+                Constraint::AtTable(a, table) => {
+                    if *table == 0 || *table > n_tables {
+                        return Err(SeatingError::UnknownTable(*table));
+                    }
+                    let a = pidx(a)?;
+                    if let Some(t) = rules.table[a].filter(|t| *t != table - 1) {
+                        // The constraint that put them at the other table first along with this one
+                        let earlier = constraints.iter()
+                            .find(|c| matches!(c, Constraint::AtTable(b, other) if pidx(b).ok() == Some(a) && *other == t + 1))
+                            .unwrap();
+                        return Err(SeatingError::Infeasible(vec![earlier.clone(), constraint.clone()]));
+                    }
+                    rules.table[a] = Some(table - 1);
+                }
+            }
+        }
+        Ok(rules)
+    }
+}
+
+// Since the table is round the first person can always take the first seat. The best happiness
+// change for seating a set of the others in a row after the first person, ending with each of
+// them, is built from the same set minus the last person. Closing the circle back to the first
+// person gives the best arrangement, which is traced back by finding the predecessors that give
+// the happiness changes.
+//
+// Someone who must sit next to another can only follow the last person in the row when all of
+// their partners already seated is that person, or the first person when they close the circle.
+fn best_cycle(input: &Input, rules: &Rules, members: &[usize]) -> Option<(i32, Vec<usize>)> {
+    let k = members.len();
+    if members.iter().any(|a| (0..input.persons.len()).any(|b| rules.next_to[*a][b] && !members.contains(&b))) {
+        return None;
+    }
+    if k < 2 {
+        return Some((0, members.to_vec()));
+    }
+    let next_to = |a: usize, b: usize| {
+        let (a, b) = (members[a], members[b]);
+        input.happiness_change_next_to[a][b] + input.happiness_change_next_to[b][a]
+    };
+
+    // The others are members 1.. at bits 0..
+    let n = k - 1;
+    let all = (1usize << n) - 1;
+    let bit = |m: usize| 1usize << (m - 1);
+    let may_follow = |seated: usize, last: usize, next: usize| {
+        !rules.apart[members[last]][members[next]] &&
+            (0..k).all(|q| q == last || !rules.next_to[members[next]][members[q]] ||
+                       if q == 0 { seated | bit(next) == all } else { seated & bit(q) == 0 })
+    };
+
+    let mut best: Vec<Option<i32>> = vec![None; (all + 1) * n];
+    for first in 1..k {
+        if may_follow(0, 0, first) {
+            best[bit(first) * n + first - 1] = Some(next_to(0, first));
+        }
+    }
+    for seated in 1..=all {
+        for last in 1..k {
+            if let Some(change) = best[seated * n + last - 1] {
+                for next in (1..k).filter(|next| seated & bit(*next) == 0 && may_follow(seated, last, *next)) {
+                    let change = change + next_to(last, next);
+                    let slot = &mut best[(seated | bit(next)) * n + next - 1];
+                    if slot.is_none_or(|best| change > best) {
+                        *slot = Some(change);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut last = (1..k)
+        .filter(|last| best[all * n + last - 1].is_some() && !rules.apart[members[*last]][members[0]])
+        .max_by_key(|last| best[all * n + last - 1].unwrap() + next_to(*last, 0))?;
+    let change = best[all * n + last - 1].unwrap() + next_to(last, 0);
+    let mut seats = vec![members[last]];
+    let mut seated = all;
+    while seated != bit(last) {
+        let before = seated & !bit(last);
+        let prev = (1..k)
+            .find(|prev| best[before * n + prev - 1].is_some_and(|change| {
+                may_follow(before, *prev, last) && change + next_to(*prev, last) == best[seated * n + last - 1].unwrap()
+            }))
+            .unwrap();
+        seats.push(members[prev]);
+        seated = before;
+        last = prev;
+    }
+    seats.push(members[0]);
+    seats.reverse();
+
+    Some((change, seats))
+}
+
+fn best_arrangement(input: &Input) -> Arrangement {
+    let n_persons = input.persons.len();
+    let (_, seats) = best_cycle(input, &Rules::none(n_persons), &(0..n_persons).collect::<Vec<_>>()).unwrap();
+    Arrangement::new(input, &seats)
+}
+
+// The happiness change, who sits at the table and in what order
+type TableChoice = (i32, usize, Vec<usize>);
+
+// The best happiness change for seating the remaining persons at the tables left, which are the
+// ones after the tables that the others already fill. Remembers which persons sit at the next
+// table and in what order.
+fn seat_tables(input: &Input, sizes: &[usize], rules: &Rules, remaining: usize,
+               memo: &mut HashMap<usize, Option<TableChoice>>) -> Option<i32> {
+    if remaining == 0 {
+        return Some(0);
+    }
+    if let Some(found) = memo.get(&remaining) {
+        return found.as_ref().map(|(change, _, _)| *change);
+    }
+
+    let n_seated = input.persons.len() - remaining.count_ones() as usize;
+    let table = (0..sizes.len()).find(|t| sizes[..*t].iter().sum::<usize>() == n_seated).unwrap();
+    let mut found: Option<TableChoice> = None;
+    let mut at_table = remaining;
+    while at_table != 0 {
+        let members: Vec<usize> = (0..input.persons.len()).filter(|p| at_table & (1 << p) != 0).collect();
+        if members.len() == sizes[table] && members.iter().all(|p| rules.table[*p].is_none_or(|t| t == table)) {
+            if let Some((change, seats)) = best_cycle(input, rules, &members) {
+                if let Some(rest) = seat_tables(input, sizes, rules, remaining & !at_table, memo) {
+                    if found.as_ref().is_none_or(|(best, _, _)| change + rest > *best) {
+                        found = Some((change + rest, at_table, seats));
+                    }
+                }
+            }
+        }
+        at_table = (at_table - 1) & remaining;
+    }
+
+    let change = found.as_ref().map(|(change, _, _)| *change);
+    memo.insert(remaining, found);
+    change
+}
+
+fn seat_at_tables(input: &Input, sizes: &[usize], rules: &Rules) -> Option<Vec<Arrangement>> {
+    let mut memo = HashMap::new();
+    let everyone = (1usize << input.persons.len()) - 1;
+    seat_tables(input, sizes, rules, everyone, &mut memo)?;
+
+    let mut arrangements = vec![];
+    let mut remaining = everyone;
+    while remaining != 0 {
+        let (_, at_table, seats) = memo[&remaining].as_ref().unwrap();
+        arrangements.push(Arrangement::new(input, seats));
+        remaining &= !at_table;
+    }
+    Some(arrangements)
+}
+
+// Seats everyone at the tables with the best total happiness change. When the constraints can't
+// all hold they are left out one at a time, keeping out those that the rest still fail without,
+// to report a minimal set that conflicts.
+fn best_tables(input: &Input, tables: &Tables) -> Result<Vec<Arrangement>, SeatingError> {
+    let n_persons = input.persons.len();
+    let sizes = if tables.sizes.is_empty() { vec![n_persons] } else { tables.sizes.clone() };
+    if let Some(table) = sizes.iter().position(|size| *size == 0) {
+        return Err(SeatingError::EmptyTable(table + 1));
+    }
+    let seats = sizes.iter().sum::<usize>();
+    if seats != n_persons {
+        return Err(SeatingError::SeatCount { seats, persons: n_persons });
+    }
+
+    let rules = Rules::new(input, sizes.len(), &tables.constraints)?;
+    if let Some(arrangements) = seat_at_tables(input, &sizes, &rules) {
+        return Ok(arrangements);
+    }
+
+    let mut conflicting = tables.constraints.clone();
+    let mut i = 0;
+    while i < conflicting.len() {
+        let mut without = conflicting.clone();
+        without.remove(i);
+        let still_infeasible = match Rules::new(input, sizes.len(), &without) {
+            Ok(rules) => seat_at_tables(input, &sizes, &rules).is_none(),
+            Err(_) => true
+        };
+        if still_infeasible {
+            conflicting = without;
+        } else {
+            i += 1;
+        }
+    }
+    Err(SeatingError::Infeasible(conflicting))
+}
+
+fn format_arrangement(arrangement: &Arrangement) -> String {
+    let mut s = arrangement.happiness_change.to_string();
+    for n in arrangement.neighbours.iter() {
+        s.push_str(&format!("\n  {} and {}: {:+} {:+}", n.persons[0], n.persons[1], n.happiness_changes[0], n.happiness_changes[1]));
+    }
+    s
+}
+
+fn format_tables(tables: Result<Vec<Arrangement>, SeatingError>) -> String {
+    match tables {
+        Ok(tables) => {
+            let mut s = String::new();
+            for (t, arrangement) in tables.iter().enumerate() {
+                s.push_str(&format!("Table {}: {}\n", t + 1, format_arrangement(arrangement)));
+            }
+            s.push_str(&format!("Total: {}", tables.iter().map(|a| a.happiness_change).sum::<i32>()));
+            s
+        },
+        Err(e) => e.to_string()
+    }
+}
+
+fn part1(input: &Input) -> Arrangement {
+    best_arrangement(input)
+}
+
+fn part2(input: &mut Input) -> Arrangement {
     input.add_yourself();
-    max_happiness_change(input)
+    best_arrangement(input)
+}
+
+struct Options {
+    input: String,
+    tables: Option<Tables>,
+    yourself: bool,
+    reference: bool
+}
+
+impl Options {
+    fn parse<I: Iterator<Item=String>>(mut args: I) -> Options {
+        let mut input = None;
+        let mut tables: Option<Tables> = None;
+        let mut yourself = false;
+        let mut reference = false;
+        while let Some(arg) = args.next() {
+            let mut pair = |what: &str| {
+                let value = args.next().unwrap_or_else(|| panic!("No {} given", what));
+                let mut parts = value.splitn(2, ',').map(|s| s.trim().to_string());
+                (parts.next().unwrap(), parts.next().unwrap_or_else(|| panic!("Expected {} as a,b", what)))
+            };
+            match arg.as_str() {
+                "--tables" => {
+                    let sizes = args.next().expect("No table sizes given");
+                    tables.get_or_insert_with(Tables::default).sizes = sizes.split(',')
+                        .map(|size| size.trim().parse::<usize>().expect("Invalid table size"))
+                        .collect();
+                },
+                "--next-to" => {
+                    let (a, b) = pair("persons");
+                    tables.get_or_insert_with(Tables::default).constraints.push(Constraint::NextTo(a, b));
+                },
+                "--apart" => {
+                    let (a, b) = pair("persons");
+                    tables.get_or_insert_with(Tables::default).constraints.push(Constraint::NotNextTo(a, b));
+                },
+                "--at" => {
+                    let (a, table) = pair("person and table");
+                    let table = table.parse::<usize>().expect("Invalid table");
+                    tables.get_or_insert_with(Tables::default).constraints.push(Constraint::AtTable(a, table));
+                },
+                "--yourself" => yourself = true,
+                "--reference" => reference = true,
+                _ => input = Some(arg)
+            }
+        }
+        Options { input: input.expect("No input file given"), tables, yourself, reference }
+    }
 }
 
 fn main() {
     measure(|| {
-        let mut input = input().expect("Input failed");
-        println!("Part1: {}", part1(&input));
-        println!("Part2: {}", part2(&mut input));
+        let options = Options::parse(env::args().skip(1));
+        let mut input = input(&options.input).expect("Input failed");
+        if let Some(tables) = options.tables.as_ref() {
+            if options.yourself {
+                input.add_yourself();
+            }
+            println!("{}", format_tables(best_tables(&input, tables)));
+        // Going through every seating only gives the happiness change
+        } else if options.reference {
+            println!("Part1: {}", max_happiness_change(&input));
+            input.add_yourself();
+            println!("Part2: {}", max_happiness_change(&input));
+        } else {
+            println!("Part1: {}", format_arrangement(&part1(&input)));
+            println!("Part2: {}", format_arrangement(&part2(&mut input)));
+        }
     });
 }
 
-fn input() -> io::Result<Input> {
-    let f = File::open(env::args().skip(1).next().expect("No input file given"))?;
+fn input(path: &str) -> io::Result<Input> {
+    let f = File::open(path)?;
     let f = BufReader::new(f);
     Ok(Input::parse(f.lines().map(|l| l.unwrap())))
 }
@@ -194,6 +594,7 @@ fn input() -> io::Result<Input> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::cmp;
     use std::collections::BTreeSet;
 
     const INPUT: &'static str =
@@ -227,13 +628,246 @@ mod tests {
         ]);
     }
 
+    fn random_input(rng: &mut Lcg, n_persons: usize) -> Input {
+        Input {
+            persons: (0..n_persons).map(|pidx| format!("P{}", pidx)).collect(),
+            happiness_change_next_to: (0..n_persons)
+                .map(|a| (0..n_persons).map(|b| if a == b { 0 } else { rng.between(-100, 100) as i32 }).collect())
+                .collect()
+        }
+    }
+
+    fn assert_valid(input: &Input, arrangement: &Arrangement) {
+        let mut seats = arrangement.seats.clone();
+        seats.sort();
+        let mut persons = input.persons.clone();
+        persons.sort();
+        assert_eq!(seats, persons);
+        let pidxs: Vec<usize> = arrangement.seats.iter().map(|p| input.persons.iter().position(|q| q == p).unwrap()).collect();
+        let expected = Arrangement::new(input, &pidxs);
+        assert_eq!(arrangement, &expected);
+    }
+
     #[test]
     fn test_part1() {
-        assert_eq!(part1(&as_input(INPUT)), 330);
+        assert_eq!(part1(&as_input(INPUT)).happiness_change, 330);
+        assert_eq!(max_happiness_change(&as_input(INPUT)), 330);
     }
 
     #[test]
     fn test_part2() {
-        assert_eq!(part2(&mut as_input(INPUT)), 286);
+        assert_eq!(part2(&mut as_input(INPUT)).happiness_change, 286);
+    }
+
+    #[test]
+    fn test_arrangement() {
+        let input = as_input(INPUT);
+        let arrangement = part1(&input);
+        assert_valid(&input, &arrangement);
+        assert!(arrangement.seats == vec!["Alice", "Bob", "Carol", "David"] ||
+                arrangement.seats == vec!["Alice", "David", "Carol", "Bob"]);
+        let mut changes: Vec<_> = arrangement.neighbours.iter()
+            .map(|n| n.happiness_changes[0] + n.happiness_changes[1])
+            .collect();
+        changes.sort();
+        assert_eq!(changes, vec![44, 53, 96, 137]);
+        assert_eq!(format_arrangement(&Arrangement::new(&input, &[0, 1])),
+                   "274\n  Alice and Bob: +54 +83\n  Bob and Alice: +83 +54");
+    }
+
+    #[test]
+    fn test_matches_seating() {
+        let mut rng = Lcg::new(13);
+        for n_persons in 0..9 {
+            for _ in 0..5 {
+                let input = random_input(&mut rng, n_persons);
+                let arrangement = best_arrangement(&input);
+                assert_valid(&input, &arrangement);
+                // The permutations start from no change at all
+                assert_eq!(cmp::max(arrangement.happiness_change, 0), max_happiness_change(&input), "{:?}", input);
+            }
+        }
+    }
+
+    // Tries every order of the persons cut into tables of the given sizes
+    fn brute_force_tables(input: &Input, sizes: &[usize], constraints: &[Constraint]) -> Option<i32> {
+        fn permute(order: &mut Vec<usize>, n: usize, check: &dyn Fn(&[usize]) -> Option<i32>, best: &mut Option<i32>) {
+            if order.len() == n {
+                if let Some(change) = check(order) {
+                    if best.is_none_or(|best| change > best) {
+                        *best = Some(change);
+                    }
+                }
+                return;
+            }
+            for p in 0..n {
+                if !order.contains(&p) {
+                    order.push(p);
+                    permute(order, n, check, best);
+                    order.pop();
+                }
+            }
+        }
+
+        let pidx = |name: &String| input.persons.iter().position(|p| p == name).unwrap();
+        let check = |order: &[usize]| -> Option<i32> {
+            let mut tables = vec![];
+            let mut start = 0;
+            for size in sizes {
+                tables.push(&order[start..start + size]);
+                start += size;
+            }
+            let table_of = |p: usize| tables.iter().position(|t| t.contains(&p)).unwrap();
+            let adjacent = |a: usize, b: usize| {
+                let table = tables[table_of(a)];
+                let i = table.iter().position(|p| *p == a).unwrap();
+                table.len() > 1 && (table[(i + 1) % table.len()] == b || table[(i + table.len() - 1) % table.len()] == b)
+            };
+            let holds = constraints.iter().all(|c| match c {
+                Constraint::NextTo(a, b) => adjacent(pidx(a), pidx(b)),
+                Constraint::NotNextTo(a, b) => !adjacent(pidx(a), pidx(b)),
+                Constraint::AtTable(a, table) => table_of(pidx(a)) == table - 1
+            });
+            if holds {
+                Some(tables.iter().map(|t| Arrangement::new(input, t).happiness_change).sum())
+            } else {
+                None
+            }
+        };
+
+        let mut best = None;
+        permute(&mut vec![], input.persons.len(), &check, &mut best);
+        best
+    }
+
+    #[test]
+    fn test_tables_match_brute_force() {
+        let mut rng = Lcg::new(44);
+        for n_persons in 1..8 {
+            for _ in 0..10 {
+                let input = random_input(&mut rng, n_persons);
+                let mut next = |max: usize| rng.between(0, max as i64 - 1) as usize;
+                let mut sizes = vec![];
+                while sizes.iter().sum::<usize>() < n_persons {
+                    let left = n_persons - sizes.iter().sum::<usize>();
+                    sizes.push(1 + next(left));
+                }
+                let mut constraints = vec![];
+                for _ in 0..next(4) {
+                    let a = input.persons[next(n_persons)].clone();
+                    let b = input.persons[next(n_persons)].clone();
+                    constraints.push(match next(3) {
+                        0 if a != b => Constraint::NextTo(a, b),
+                        1 => Constraint::NotNextTo(a, b),
+                        _ => Constraint::AtTable(a, 1 + next(sizes.len()))
+                    });
+                }
+
+                let tables = Tables { sizes: sizes.clone(), constraints: constraints.clone() };
+                let expected = brute_force_tables(&input, &sizes, &constraints);
+                match best_tables(&input, &tables) {
+                    Ok(arrangements) => {
+                        let total = arrangements.iter().map(|a| a.happiness_change).sum::<i32>();
+                        assert_eq!(Some(total), expected, "{:?} {:?}", input, tables);
+                        assert_eq!(arrangements.iter().map(|a| a.seats.len()).collect::<Vec<_>>(), sizes);
+                        for (t, arrangement) in arrangements.iter().enumerate() {
+                            for constraint in constraints.iter() {
+                                if let Constraint::AtTable(a, table) = constraint {
+                                    assert_eq!(arrangement.seats.contains(a), *table == t + 1);
+                                }
+                            }
+                        }
+                    },
+                    Err(SeatingError::Infeasible(conflicting)) => {
+                        assert_eq!(expected, None, "{:?} {:?}", input, tables);
+                        assert!(!conflicting.is_empty());
+                        assert_eq!(brute_force_tables(&input, &sizes, &conflicting), None);
+                        for i in 0..conflicting.len() {
+                            let mut without = conflicting.clone();
+                            without.remove(i);
+                            assert!(brute_force_tables(&input, &sizes, &without).is_some());
+                        }
+                    },
+                    Err(e) => panic!("{}", e)
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_tables() {
+        let input = as_input(INPUT);
+        let tables = Tables { sizes: vec![4], constraints: vec![] };
+        assert_eq!(best_tables(&input, &tables).unwrap(), vec![part1(&input)]);
+
+        let tables = Tables {
+            sizes: vec![2, 2],
+            constraints: vec![Constraint::NotNextTo("Alice".to_string(), "Bob".to_string())]
+        };
+        let arrangements = best_tables(&input, &tables).unwrap();
+        assert_eq!(arrangements.iter().map(|a| a.happiness_change).sum::<i32>(), 2 * (46 - 2) + 2 * (60 - 7));
+        assert_eq!(format_tables(Ok(arrangements)),
+                   "Table 1: 88\n  Alice and David: -2 +46\n  David and Alice: +46 -2\n\
+                    Table 2: 106\n  Bob and Carol: -7 +60\n  Carol and Bob: +60 -7\n\
+                    Total: 194");
+    }
+
+    #[test]
+    fn test_infeasible() {
+        let input = as_input(INPUT);
+        let constraint = |c: &str, a: &str, b: &str| match c {
+            "next to" => Constraint::NextTo(a.to_string(), b.to_string()),
+            "not next to" => Constraint::NotNextTo(a.to_string(), b.to_string()),
+            _ => Constraint::AtTable(a.to_string(), b.parse().unwrap())
+        };
+
+        let tables = Tables { sizes: vec![4], constraints: vec![
+            constraint("next to", "Alice", "Bob"),
+            constraint("not next to", "Carol", "David"),
+            constraint("next to", "Alice", "Carol"),
+            constraint("next to", "Alice", "David")
+        ] };
+        let error = best_tables(&input, &tables).unwrap_err();
+        assert_eq!(error.to_string(), "these constraints can't all hold: Alice next to Bob, Alice next to Carol, Alice next to David");
+
+        let tables = Tables { sizes: vec![2, 2], constraints: vec![
+            constraint("at", "Alice", "2"),
+            constraint("not next to", "Alice", "Carol"),
+            constraint("at", "Bob", "1"),
+            constraint("next to", "Bob", "Alice")
+        ] };
+        assert_eq!(best_tables(&input, &tables).unwrap_err(), SeatingError::Infeasible(vec![
+            constraint("at", "Alice", "2"),
+            constraint("at", "Bob", "1"),
+            constraint("next to", "Bob", "Alice")
+        ]));
+
+        let tables = Tables { sizes: vec![2, 2], constraints: vec![constraint("at", "Alice", "2"), constraint("at", "Alice", "1")] };
+        assert_eq!(best_tables(&input, &tables).unwrap_err().to_string(), "these constraints can't all hold: Alice at table 2, Alice at table 1");
+        // Only one of the same constraint given twice is needed for the conflict
+        let tables = Tables { sizes: vec![2, 2], constraints: vec![
+            constraint("at", "Alice", "2"), constraint("at", "Alice", "2"), constraint("at", "Alice", "1")
+        ] };
+        assert_eq!(best_tables(&input, &tables).unwrap_err().to_string(), "these constraints can't all hold: Alice at table 2, Alice at table 1");
+    }
+
+    #[test]
+    fn test_seating_errors() {
+        let input = as_input(INPUT);
+        let tables = |sizes: Vec<usize>, constraints: Vec<Constraint>| Tables { sizes, constraints };
+        assert_eq!(best_tables(&input, &tables(vec![2, 3], vec![])).unwrap_err(), SeatingError::SeatCount { seats: 5, persons: 4 });
+        assert_eq!(best_tables(&input, &tables(vec![4, 0], vec![])).unwrap_err().to_string(), "table 2 has no seats");
+        assert_eq!(best_tables(&input, &tables(vec![2, 2], vec![Constraint::AtTable("Alice".to_string(), 3)])).unwrap_err(),
+                   SeatingError::UnknownTable(3));
+        assert_eq!(best_tables(&input, &tables(vec![], vec![Constraint::NextTo("Alice".to_string(), "Zed".to_string())])).unwrap_err().to_string(),
+                   "unknown person Zed");
+    }
+
+    #[test]
+    fn test_many_guests() {
+        let input = random_input(&mut Lcg::new(2015), 16);
+        let arrangement = best_arrangement(&input);
+        assert_valid(&input, &arrangement);
+        assert_eq!(arrangement.seats[0], "P0");
     }
 }
\ No newline at end of file
@@ -4,6 +4,8 @@ extern crate utils;
 
 use std::env;
 use std::cmp;
+use std::error;
+use std::fmt;
 use std::str::FromStr;
 use std::num::ParseIntError;
 use std::io::{self, BufReader};
@@ -12,60 +14,101 @@ use std::fs::File;
 use regex::Regex;
 use utils::*;
 
-type Input = Vec<Ingredient>;
+// The ingredients and the names of their properties, in the order the first ingredient lists them
+#[derive(Debug)]
+struct Input {
+    properties: Vec<String>,
+    ingredients: Vec<Ingredient>
+}
 
 #[derive(Debug)]
 struct Ingredient {
     name: String,
-    capacity: i32,
-    durability: i32,
-    flavor: i32,
-    texture: i32,
-    calories: i32
+    // In the order of Input::properties
+    values: Vec<i32>
 }
 
-impl FromStr for Ingredient {
-    type Err = ParseIntError;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        lazy_static! {
-            static ref RE: Regex = Regex::new(r"^(.+): .+ (.+), .+ (.+), .+ (.+), .+ (.+), .+ (.+)$").unwrap();
-        }
-
-        let caps = RE.captures(s).unwrap();
-        let get = |idx| caps.get(idx).unwrap().as_str().parse::<i32>().unwrap();
-        Ok(Ingredient {
-            name: caps.get(1).unwrap().as_str().into(),
-            capacity: get(2),
-            durability: get(3),
-            flavor: get(4),
-            texture: get(5),
-            calories: get(6)
-        })
+#[derive(Debug, PartialEq)]
+struct InputError {
+    line: usize,
+    message: String
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
     }
 }
 
-fn score_mix(input: &Input, distribution: &Vec<usize>) -> i32 {
-    let capacity = distribution.iter().enumerate().map(|(i, &d)| input[i].capacity * d as i32).sum::<i32>();
-    let durability = distribution.iter().enumerate().map(|(i, &d)| input[i].durability * d as i32).sum::<i32>();
-    let flavor = distribution.iter().enumerate().map(|(i, &d)| input[i].flavor * d as i32).sum::<i32>();
-    let texture = distribution.iter().enumerate().map(|(i, &d)| input[i].texture * d as i32).sum::<i32>();
+impl error::Error for InputError {}
+
+impl Input {
+    fn parse(lines: impl IntoIterator<Item = String>) -> Result<Input, InputError> {
+        lazy_static! {
+            static ref INGREDIENT_RE: Regex = Regex::new(r"^(.+?):\s*(.*)$").unwrap();
+            static ref PROPERTY_RE: Regex = Regex::new(r"^(\w+)\s+(-?\d+)$").unwrap();
+        }
+
+        let mut input = Input { properties: vec![], ingredients: vec![] };
+        for (i, l) in lines.into_iter().enumerate() {
+            let line = i + 1;
+            let error = |message: String| InputError { line, message };
+            if l.trim().is_empty() {
+                continue;
+            }
+
+            let caps = INGREDIENT_RE.captures(l.trim()).ok_or_else(|| error(format!("expected an ingredient, got {}", l.trim())))?;
+            let name = caps.get(1).unwrap().as_str().to_string();
+            let mut properties: Vec<(String, i32)> = vec![];
+            for property in caps.get(2).unwrap().as_str().split(',').map(|p| p.trim()) {
+                let caps = PROPERTY_RE.captures(property).ok_or_else(|| error(format!("expected a property and a value, got {}", property)))?;
+                let property = caps.get(1).unwrap().as_str().to_string();
+                if properties.iter().any(|(p, _)| *p == property) {
+                    return Err(error(format!("{} has {} more than once", name, property)));
+                }
+                let value = caps.get(2).unwrap().as_str().parse::<i32>().map_err(|e| error(e.to_string()))?;
+                properties.push((property, value));
+            }
+
+            if input.ingredients.is_empty() {
+                input.properties = properties.iter().map(|(p, _)| p.clone()).collect();
+            }
+            if let Some((unknown, _)) = properties.iter().find(|(p, _)| !input.properties.contains(p)) {
+                return Err(error(format!("{} has {} which {} doesn't", name, unknown, input.ingredients[0].name)));
+            }
+            let values = input.properties.iter()
+                .map(|property| properties.iter().find(|(p, _)| p == property).map(|(_, value)| *value)
+                    .ok_or_else(|| error(format!("{} has no {}", name, property))))
+                .collect::<Result<_, _>>()?;
+            input.ingredients.push(Ingredient { name, values });
+        }
+        Ok(input)
+    }
+
+    fn property(&self, name: &str) -> Result<usize, RecipeError> {
+        self.properties.iter().position(|p| p == name).ok_or_else(|| RecipeError::UnknownProperty(name.to_string()))
+    }
+}
 
-    cmp::max(0, capacity) * cmp::max(0, durability) * cmp::max(0, flavor) * cmp::max(0, texture)
+fn score_mix(input: &Input, scored: &[usize], distribution: &[usize]) -> i128 {
+    scored.iter()
+        .map(|p| cmp::max(0, property_mix(input, *p, distribution)) as i128)
+        .product()
 }
 
-fn calories_mix(input: &Input, distribution: &Vec<usize>) -> i32 {
-    distribution.iter().enumerate().map(|(i, &d)| input[i].calories * d as i32 ).sum::<i32>()
+fn property_mix(input: &Input, property: usize, distribution: &[usize]) -> i32 {
+    distribution.iter().enumerate().map(|(i, &d)| input.ingredients[i].values[property] * d as i32).sum::<i32>()
 }
 
-fn find_best_mix(input: &Input, distribution: &Vec<usize>, to_distribute: usize, calories: i32) -> (i32, i32) {
+fn find_best_mix(input: &Input, scored: &[usize], constrained: usize, distribution: &[usize], to_distribute: usize, target: i32) -> (i128, i128) {
 
-    if distribution.len() == input.len() - 1 {
-        let mut distribution = distribution.clone();
+    if distribution.len() == input.ingredients.len() - 1 {
+        let mut distribution = distribution.to_vec();
         distribution.push(to_distribute);
-        let score = score_mix(input, &distribution);
-        (score, if calories_mix(input, &distribution) == calories { score } else { 0 })
+        let score = score_mix(input, scored, &distribution);
+        (score, if property_mix(input, constrained, &distribution) == target { score } else { 0 })
     } else {
-        let mut distribution = distribution.clone();
+        let mut distribution = distribution.to_vec();
         let didx = distribution.len();
         distribution.push(0);
         let mut best_s1 = 0;
@@ -73,7 +116,7 @@ fn find_best_mix(input: &Input, distribution: &Vec<usize>, to_distribute: usize,
 
         for i in 0..=to_distribute {
             distribution[didx] = i;
-            let (s1, s2) = find_best_mix(input, &distribution, to_distribute - i, calories);
+            let (s1, s2) = find_best_mix(input, scored, constrained, &distribution, to_distribute - i, target);
             if s1 > best_s1 {
                 best_s1 = s1;
             }
@@ -85,19 +128,379 @@ fn find_best_mix(input: &Input, distribution: &Vec<usize>, to_distribute: usize,
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Equal,
+    AtLeast,
+    AtMost
+}
+
+// A requirement on the total of a property over the whole mix, like `calories == 500`
+#[derive(Debug, Clone, PartialEq)]
+struct Constraint {
+    property: String,
+    comparison: Comparison,
+    value: i32
+}
+
+impl Constraint {
+    // Whether a total somewhere between min and max can meet the constraint
+    fn reachable(&self, min: i32, max: i32) -> bool {
+        match self.comparison {
+            Comparison::Equal => min <= self.value && self.value <= max,
+            Comparison::AtLeast => max >= self.value,
+            Comparison::AtMost => min <= self.value
+        }
+    }
+}
+
+impl FromStr for Constraint {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        lazy_static! {
+            static ref RE: Regex = Regex::new(r"^\s*(\w+)\s*(==|>=|<=)\s*(-?\d+)\s*$").unwrap();
+        }
+
+        let caps = RE.captures(s).ok_or_else(|| format!("invalid constraint {}", s))?;
+        Ok(Constraint {
+            property: caps.get(1).unwrap().as_str().to_string(),
+            comparison: match caps.get(2).unwrap().as_str() {
+                "==" => Comparison::Equal,
+                ">=" => Comparison::AtLeast,
+                _ => Comparison::AtMost
+            },
+            value: caps.get(3).unwrap().as_str().parse().map_err(|e: ParseIntError| e.to_string())?
+        })
+    }
+}
+
+// The properties whose totals are multiplied into the score and the constraints the mix has to meet
+#[derive(Debug, Clone, PartialEq)]
+struct Scoring {
+    multiply: Vec<String>,
+    constraints: Vec<Constraint>
+}
+
+impl Scoring {
+    // Every property but calories counts, like in the puzzle
+    fn without_calories(input: &Input) -> Scoring {
+        Scoring { multiply: input.properties.iter().filter(|p| *p != "calories").cloned().collect(), constraints: vec![] }
+    }
+}
+
+// How many teaspoons of each ingredient to use and the score of that mix
+#[derive(Debug, PartialEq)]
+struct Recipe {
+    teaspoons: Vec<(String, usize)>,
+    score: i128
+}
+
+impl fmt::Display for Recipe {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let teaspoons: Vec<_> = self.teaspoons.iter().map(|(name, n)| format!("{} {}", name, n)).collect();
+        write!(f, "{} ({})", self.score, teaspoons.join(", "))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum RecipeError {
+    UnknownProperty(String),
+    NoIngredients,
+    NoMix,
+    Overflow
+}
+
+impl fmt::Display for RecipeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecipeError::UnknownProperty(name) => write!(f, "no ingredient has {}", name),
+            RecipeError::NoIngredients => write!(f, "there are no ingredients"),
+            RecipeError::NoMix => write!(f, "no mix meets the constraints"),
+            RecipeError::Overflow => write!(f, "the totals or the score of a mix can get too large")
+        }
+    }
+}
+
+impl error::Error for RecipeError {}
+
+struct BestMix {
+    score: i128,
+    teaspoons: Vec<usize>,
+    totals: Vec<i32>
+}
+
+struct MixSearch<'a> {
+    input: &'a Input,
+    // Property indexes
+    scored: Vec<usize>,
+    constraints: Vec<(usize, &'a Constraint)>,
+    // The lowest and highest value of each property among the ingredients from an index on
+    suffix_min: Vec<Vec<i32>>,
+    suffix_max: Vec<Vec<i32>>
+}
+
+impl<'a> MixSearch<'a> {
+    // Tries the amounts of the next ingredient with the most promising first and stops once the
+    // rest can't beat the best score
+    fn find(&self, next: usize, left: usize, totals: &[i32], teaspoons: &mut Vec<usize>, best: &mut Option<BestMix>) {
+        // The last ingredient takes what's left
+        if next == self.input.ingredients.len() - 1 {
+            let totals = self.add(totals, next, left);
+            if let Some(score) = self.score(&totals) {
+                if best.as_ref().is_none_or(|best| score > best.score) {
+                    let mut teaspoons = teaspoons.clone();
+                    teaspoons.push(left);
+                    *best = Some(BestMix { score, teaspoons, totals });
+                }
+            }
+            return;
+        }
+
+        let mut amounts: Vec<(f64, usize)> = (0..=left)
+            .filter_map(|n| self.bound(next + 1, left - n, &self.add(totals, next, n), best.as_ref()).map(|bound| (bound, n)))
+            .collect();
+        amounts.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+        for (bound, n) in amounts {
+            if best.as_ref().is_some_and(|best| bound < (best.score + 1) as f64) {
+                break;
+            }
+            teaspoons.push(n);
+            self.find(next + 1, left - n, &self.add(totals, next, n), teaspoons, best);
+            teaspoons.pop();
+        }
+    }
+
+    // The score of a whole mix, None when it doesn't meet the constraints
+    fn score(&self, totals: &[i32]) -> Option<i128> {
+        if self.constraints.iter().all(|(p, c)| c.reachable(totals[*p], totals[*p])) {
+            Some(self.scored.iter().map(|p| cmp::max(0, totals[*p]) as i128).product::<i128>())
+        } else {
+            None
+        }
+    }
+
+    // The highest score possible when the teaspoons left go to the ingredients from next on, None
+    // when the constraints can't be met. Each total ends up between the teaspoons left times the
+    // lowest and the highest value among those ingredients.
+    fn bound(&self, next: usize, left: usize, totals: &[i32], best: Option<&BestMix>) -> Option<f64> {
+        if next == self.input.ingredients.len() - 1 {
+            return self.score(&self.add(totals, next, left)).map(|score| score as f64);
+        }
+
+        let left_i = left as i32;
+        let range = |p: usize| (totals[p] + left_i * self.suffix_min[next][p], totals[p] + left_i * self.suffix_max[next][p]);
+        if !self.constraints.iter().all(|(p, c)| {
+            let (min, max) = range(*p);
+            c.reachable(min, max)
+        }) {
+            return None;
+        }
+        if self.scored.is_empty() {
+            return Some(1.0);
+        }
+        let highest: Vec<i32> = self.scored.iter().map(|p| range(*p).1).collect();
+        if highest.iter().any(|h| *h <= 0) {
+            return Some(0.0);
+        }
+        let mut bound = self.score_bound(next, left, totals, &highest);
+        if let Some(best) = best.filter(|best| self.scored.iter().all(|p| best.totals[*p] > 0)) {
+            let best_totals: Vec<i32> = self.scored.iter().map(|p| best.totals[*p]).collect();
+            bound = bound.min(self.score_bound(next, left, totals, &best_totals));
+        }
+        Some(bound)
+    }
+
+    // When every scored total y is positive the weighted AM-GM inequality bounds their product by
+    // (sum of w * y / k)^k / product of w for any positive weights w, with k totals. The bound is
+    // tight when all w * y are equal, so the weights are one over totals a good mix could have:
+    // the highest possible, which keeps the bound below the product of those, or the best mix's
+    // so far. The weighted sum is linear in the teaspoons so its highest value is found at a
+    // corner of the possible mixes, taking one constraint at a time into account.
+    fn score_bound(&self, next: usize, left: usize, totals: &[i32], expected: &[i32]) -> f64 {
+        let weights: Vec<f64> = expected.iter().map(|e| 1.0 / *e as f64).collect();
+        let weighted = |values: &[i32]| self.scored.iter().zip(weights.iter())
+            .map(|(p, w)| w * values[*p] as f64)
+            .sum::<f64>();
+        let gains: Vec<f64> = self.input.ingredients[next..].iter().map(|i| weighted(&i.values)).collect();
+        let highest_gain = self.constraints.iter()
+            .map(|c| self.highest_gain(next, left, &gains, Some(*c), totals))
+            .fold(self.highest_gain(next, left, &gains, None, totals), |a, b| a.and_then(|a| b.map(|b| a.min(b))));
+
+        let sum = match highest_gain {
+            Some(gain) => weighted(totals) + gain,
+            None => return 0.0
+        };
+        if sum <= 0.0 {
+            return 0.0;
+        }
+        let k = self.scored.len() as i32;
+        (sum / k as f64).powi(k) / weights.iter().product::<f64>() * (1.0 + 1e-9)
+    }
+
+    // The highest total gain from spreading the teaspoons left over the ingredients from next on
+    // when the mix has to meet the constraint, None when it can't. With a single constraint the
+    // best mix uses one ingredient or two that together meet the constraint exactly.
+    fn highest_gain(&self, next: usize, left: usize, gains: &[f64], constraint: Option<(usize, &Constraint)>, totals: &[i32]) -> Option<f64> {
+        let left = left as f64;
+        let (values, needed, comparison) = match constraint {
+            Some((p, c)) => {
+                let values: Vec<f64> = self.input.ingredients[next..].iter().map(|i| i.values[p] as f64).collect();
+                (values, (c.value - totals[p]) as f64, c.comparison)
+            },
+            None => return gains.iter().map(|g| g * left).reduce(f64::max)
+        };
+        let meets = |total: f64| match comparison {
+            Comparison::Equal => total == needed,
+            Comparison::AtLeast => total >= needed,
+            Comparison::AtMost => total <= needed
+        };
+
+        let mut best: Option<f64> = None;
+        let mut consider = |gain: f64| best = Some(best.map_or(gain, |b| b.max(gain)));
+        for j in 0..gains.len() {
+            if meets(values[j] * left) {
+                consider(gains[j] * left);
+            }
+            for k in (j + 1)..gains.len() {
+                if values[j] != values[k] {
+                    let on_j = (needed - left * values[k]) / (values[j] - values[k]);
+                    if (0.0..=left).contains(&on_j) {
+                        consider(gains[j] * on_j + gains[k] * (left - on_j));
+                    }
+                }
+            }
+        }
+        best
+    }
+
+    fn add(&self, totals: &[i32], ingredient: usize, teaspoons: usize) -> Vec<i32> {
+        totals.iter().zip(self.input.ingredients[ingredient].values.iter())
+            .map(|(total, value)| total + value * teaspoons as i32)
+            .collect()
+    }
+}
+
+// Makes sure that every total fits in an i32 and every score in an i128 before mixing, so the
+// search doesn't have to check each addition and multiplication
+fn check_overflow(input: &Input, teaspoons: usize, scored: &[usize]) -> Result<(), RecipeError> {
+    if teaspoons as i128 > i32::MAX as i128 {
+        return Err(RecipeError::Overflow);
+    }
+    let mut highest_score: i128 = 1;
+    for p in 0..input.properties.len() {
+        let largest = input.ingredients.iter().map(|i| (i.values[p] as i128).abs()).max().unwrap_or(0);
+        let total = largest * teaspoons as i128;
+        if total > i32::MAX as i128 {
+            return Err(RecipeError::Overflow);
+        }
+        if scored.contains(&p) {
+            highest_score = highest_score.checked_mul(total).ok_or(RecipeError::Overflow)?;
+        }
+    }
+    Ok(())
+}
+
+// The mix of the given number of teaspoons with the highest score that meets all the constraints
+fn best_recipe(input: &Input, teaspoons: usize, scoring: &Scoring) -> Result<Recipe, RecipeError> {
+    if input.ingredients.is_empty() {
+        return Err(RecipeError::NoIngredients);
+    }
+    let scored: Vec<usize> = scoring.multiply.iter().map(|p| input.property(p)).collect::<Result<_, _>>()?;
+    check_overflow(input, teaspoons, &scored)?;
+    let constraints = scoring.constraints.iter().map(|c| input.property(&c.property).map(|p| (p, c))).collect::<Result<_, _>>()?;
+
+    let n_properties = input.properties.len();
+    let mut suffix_min = vec![vec![i32::MAX; n_properties]; input.ingredients.len() + 1];
+    let mut suffix_max = vec![vec![i32::MIN; n_properties]; input.ingredients.len() + 1];
+    for i in (0..input.ingredients.len()).rev() {
+        for p in 0..n_properties {
+            let value = input.ingredients[i].values[p];
+            suffix_min[i][p] = cmp::min(suffix_min[i + 1][p], value);
+            suffix_max[i][p] = cmp::max(suffix_max[i + 1][p], value);
+        }
+    }
+
+    let search = MixSearch { input, scored, constraints, suffix_min, suffix_max };
+    let mut best = None;
+    search.find(0, teaspoons, &vec![0; n_properties], &mut vec![], &mut best);
+    best.map(|best| Recipe {
+        teaspoons: input.ingredients.iter().zip(best.teaspoons).map(|(i, n)| (i.name.clone(), n)).collect(),
+        score: best.score
+    }).ok_or(RecipeError::NoMix)
+}
+
+fn format_recipe(recipe: Result<Recipe, RecipeError>) -> String {
+    match recipe {
+        Ok(recipe) => recipe.to_string(),
+        Err(e) => e.to_string()
+    }
+}
+
+struct Options {
+    input: String,
+    teaspoons: usize,
+    multiply: Option<Vec<String>>,
+    constraints: Vec<Constraint>,
+    reference: bool
+}
+
+impl Options {
+    fn parse<I: Iterator<Item=String>>(mut args: I) -> Options {
+        let mut input = None;
+        let mut teaspoons = 100;
+        let mut multiply = None;
+        let mut constraints: Vec<Constraint> = vec![];
+        let mut reference = false;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--teaspoons" => teaspoons = args.next().and_then(|n| n.parse().ok()).expect("Invalid number of teaspoons"),
+                "--score" => multiply = Some(args.next().expect("No properties given").split(',').map(|p| p.trim().to_string()).collect()),
+                "--constraint" => constraints.push(args.next().expect("No constraint given").parse().unwrap_or_else(|e| panic!("{}", e))),
+                "--reference" => reference = true,
+                _ => input = Some(arg)
+            }
+        }
+        // The puzzle's part 2 unless asked for something else
+        if constraints.is_empty() {
+            constraints.push("calories == 500".parse().unwrap());
+        }
+        // Going through every mix only knows about a single property that has to add up exactly
+        if reference && !(constraints.len() == 1 && constraints[0].comparison == Comparison::Equal) {
+            panic!("--reference only supports a single constraint with ==");
+        }
+        Options { input: input.expect("No input file given"), teaspoons, multiply, constraints, reference }
+    }
+}
+
 fn main() {
     measure(|| {
-        let input = input().expect("Input failed");
-        let (part1, part2) = find_best_mix(&input, &vec![], 100, 500);
-        println!("Part1: {}", part1);
-        println!("Part2: {}", part2);
+        let options = Options::parse(env::args().skip(1));
+        let input = input(&options.input).expect("Input failed");
+        let mut scoring = Scoring::without_calories(&input);
+        if let Some(multiply) = options.multiply.as_ref() {
+            scoring.multiply = multiply.clone();
+        }
+        if options.reference {
+            let scored: Vec<usize> = scoring.multiply.iter().map(|p| input.property(p).expect("Unknown property")).collect();
+            let constraint = &options.constraints[0];
+            let constrained = input.property(&constraint.property).expect("Unknown property");
+            check_overflow(&input, options.teaspoons, &scored).unwrap_or_else(|e| panic!("{}", e));
+            let (part1, part2) = find_best_mix(&input, &scored, constrained, &[], options.teaspoons, constraint.value);
+            println!("Part1: {}", part1);
+            println!("Part2: {}", part2);
+        } else {
+            println!("Part1: {}", format_recipe(best_recipe(&input, options.teaspoons, &scoring)));
+            scoring.constraints = options.constraints.clone();
+            println!("Part2: {}", format_recipe(best_recipe(&input, options.teaspoons, &scoring)));
+        }
     });
 }
 
-fn input() -> io::Result<Input> {
-    let f = File::open(env::args().skip(1).next().expect("No input file given"))?;
+fn input(path: &str) -> io::Result<Input> {
+    let f = File::open(path)?;
     let f = BufReader::new(f);
-    Ok(f.lines().map(|l| l.unwrap().parse::<Ingredient>().unwrap()).collect())
+    let lines = f.lines().collect::<io::Result<Vec<_>>>()?;
+    Input::parse(lines).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
 }
 
 #[cfg(test)]
@@ -109,18 +512,165 @@ mod tests {
         Cinnamon: capacity 2, durability 3, flavor -2, texture -1, calories 3";
 
     fn as_input(s: &str) -> Input {
-        s.split('\n').map(|s| s.trim().parse::<Ingredient>().unwrap()).collect()
+        Input::parse(s.split('\n').map(|s| s.trim().into())).unwrap()
+    }
+
+    fn scoring(input: &Input, constraints: &[&str]) -> Scoring {
+        Scoring { constraints: constraints.iter().map(|c| c.parse().unwrap()).collect(), ..Scoring::without_calories(input) }
     }
 
     #[test]
     fn test_part1() {
-        let (part1, _) = find_best_mix(&as_input(INPUT), &vec![], 100, 500);
+        let (part1, _) = find_best_mix(&as_input(INPUT), &[0, 1, 2, 3], 4, &[], 100, 500);
         assert_eq!(part1, 62842880);
     }
 
     #[test]
     fn test_part2() {
-        let (_, part2) = find_best_mix(&as_input(INPUT), &vec![], 100, 500);
+        let (_, part2) = find_best_mix(&as_input(INPUT), &[0, 1, 2, 3], 4, &[], 100, 500);
         assert_eq!(part2, 57600000);
     }
-}
\ No newline at end of file
+
+    // Every way to spread the teaspoons over the ingredients
+    fn brute_force(input: &Input, teaspoons: usize, scoring: &Scoring) -> Option<i128> {
+        fn mixes(n: usize, teaspoons: usize) -> Vec<Vec<usize>> {
+            if n == 1 {
+                return vec![vec![teaspoons]];
+            }
+            (0..=teaspoons)
+                .flat_map(|first| mixes(n - 1, teaspoons - first).into_iter().map(move |mut rest| {
+                    rest.insert(0, first);
+                    rest
+                }))
+                .collect()
+        }
+
+        mixes(input.ingredients.len(), teaspoons).iter()
+            .filter_map(|mix| {
+                let total = |p: &String| property_mix(input, input.property(p).unwrap(), mix);
+                if scoring.constraints.iter().all(|c| c.reachable(total(&c.property), total(&c.property))) {
+                    Some(scoring.multiply.iter().map(|p| cmp::max(0, total(p)) as i128).product::<i128>())
+                } else {
+                    None
+                }
+            })
+            .max()
+    }
+
+    fn random_input(rng: &mut Lcg, n: usize) -> Input {
+        let mut next = |lowest: i64, highest: i64| rng.between(lowest, highest);
+        let lines: Vec<String> = (0..n)
+            .map(|i| format!("I{}: capacity {}, durability {}, flavor {}, texture {}, calories {}",
+                             i, next(-3, 5), next(-3, 5), next(-3, 5), next(-3, 5), next(1, 9)))
+            .collect();
+        Input::parse(lines).unwrap()
+    }
+
+    #[test]
+    fn test_best_recipe() {
+        let input = as_input(INPUT);
+        let recipe = best_recipe(&input, 100, &scoring(&input, &[])).unwrap();
+        assert_eq!(recipe.score, 62842880);
+        assert_eq!(recipe.to_string(), "62842880 (Butterscotch 44, Cinnamon 56)");
+        assert_eq!(best_recipe(&input, 100, &scoring(&input, &["calories == 500"])).unwrap().to_string(),
+                   "57600000 (Butterscotch 40, Cinnamon 60)");
+        assert_eq!(best_recipe(&input, 100, &scoring(&input, &["calories==250"])), Err(RecipeError::NoMix));
+        assert_eq!(best_recipe(&input, 100, &scoring(&input, &["sweetness >= 3"])).unwrap_err().to_string(),
+                   "no ingredient has sweetness");
+    }
+
+    #[test]
+    fn test_matches_brute_force() {
+        let mut rng = Lcg::new(15);
+        let constraints: Vec<Vec<&str>> = vec![
+            vec![],
+            vec!["calories == 150"],
+            vec!["texture >= 20", "calories <= 120"],
+            vec!["flavor <= 40", "capacity >= 10", "calories == 100"]
+        ];
+        for n in 1..5 {
+            for _ in 0..10 {
+                let input = random_input(&mut rng, n);
+                for constraints in constraints.iter() {
+                    let scoring = scoring(&input, constraints);
+                    let expected = brute_force(&input, 30, &scoring);
+                    let recipe = best_recipe(&input, 30, &scoring);
+                    assert_eq!(recipe.as_ref().map(|r| r.score).ok(), expected, "{:?} {:?}", input, constraints);
+                    if let Ok(recipe) = recipe {
+                        assert_eq!(recipe.teaspoons.iter().map(|(_, n)| n).sum::<usize>(), 30);
+                    }
+                }
+                if n > 1 {
+                    assert_eq!(best_recipe(&input, 30, &scoring(&input, &constraints[1])).map_or(0, |r| r.score),
+                               find_best_mix(&input, &[0, 1, 2, 3], 4, &[], 30, 150).1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_wide_properties() {
+        // Eight totals of up to 500 multiply to more than an i64 holds
+        let properties = ["a", "b", "c", "d", "e", "f", "g", "h"];
+        let line = |name: &str, values: &[i32]| format!("{}: {}", name, properties.iter().zip(values)
+            .map(|(p, v)| format!("{} {}", p, v)).collect::<Vec<_>>().join(", "));
+        let input = Input::parse(vec![line("Even", &[5, 5, 5, 5, 5, 5, 5, 5]), line("Odd", &[4, 6, 4, 6, 4, 6, 4, 6])]).unwrap();
+        let scoring = Scoring::without_calories(&input);
+        let recipe = best_recipe(&input, 100, &scoring).unwrap();
+        assert!(recipe.score > i64::MAX as i128);
+        assert_eq!(Some(recipe.score), brute_force(&input, 100, &scoring));
+        assert_eq!(recipe.score, 500i128.pow(8));
+
+        // Twenty totals of up to 100000 don't fit in an i128 and neither do totals beyond an i32
+        let wide = Input::parse(vec![format!("Only: {}", (0..20).map(|p| format!("p{} 1000", p)).collect::<Vec<_>>().join(", "))]).unwrap();
+        assert_eq!(best_recipe(&wide, 100, &Scoring::without_calories(&wide)), Err(RecipeError::Overflow));
+        assert_eq!(best_recipe(&input, 500_000_000, &scoring), Err(RecipeError::Overflow));
+        assert_eq!(best_recipe(&input, 100, &Scoring { multiply: vec!["a".to_string()], constraints: vec![] }).map(|r| r.score), Ok(500));
+    }
+
+    #[test]
+    fn test_many_ingredients() {
+        let input = random_input(&mut Lcg::new(2015), 10);
+        let recipe = best_recipe(&input, 100, &scoring(&input, &["calories == 500"])).unwrap();
+        assert_eq!(recipe.teaspoons.len(), 10);
+        assert_eq!(recipe.teaspoons.iter().map(|(_, n)| n).sum::<usize>(), 100);
+        let calories = recipe.teaspoons.iter().zip(input.ingredients.iter()).map(|((_, n), i)| i.values[4] * *n as i32).sum::<i32>();
+        assert_eq!(calories, 500);
+    }
+
+    #[test]
+    fn test_properties() {
+        let input = as_input(
+           "Sugar: sweetness 5, crunch -1, calories 4
+            Oats: crunch 3, calories 2, sweetness 1
+            Salt: calories 0, sweetness -2, crunch 1");
+        assert_eq!(input.properties, vec!["sweetness", "crunch", "calories"]);
+        assert_eq!(input.ingredients[1].values, vec![1, 3, 2]);
+
+        let scoring = Scoring { multiply: vec!["sweetness".to_string(), "crunch".to_string()], constraints: vec![] };
+        assert_eq!(best_recipe(&input, 10, &scoring).map(|r| r.score).ok(), brute_force(&input, 10, &scoring));
+        let scoring = Scoring { constraints: vec!["calories <= 20".parse().unwrap()], ..scoring };
+        assert_eq!(best_recipe(&input, 10, &scoring).map(|r| r.score).ok(), brute_force(&input, 10, &scoring));
+        let scoring = Scoring { multiply: vec!["crunch".to_string()], constraints: vec!["sweetness == 12".parse().unwrap()] };
+        assert_eq!(best_recipe(&input, 10, &scoring).unwrap().to_string(), "18 (Sugar 2, Oats 6, Salt 2)");
+    }
+
+    #[test]
+    fn test_input_errors() {
+        let parse = |s: &str| Input::parse(s.split('\n').map(|s| s.trim().into())).unwrap_err().to_string();
+        assert_eq!(parse("Sugar: sweetness 5, crunch -1\nOats: crunch 3"), "line 2: Oats has no sweetness");
+        assert_eq!(parse("Sugar: sweetness 5\nOats: sweetness 3, crunch 3"), "line 2: Oats has crunch which Sugar doesn't");
+        assert_eq!(parse("Sugar: sweetness 5, sweetness 3"), "line 1: Sugar has sweetness more than once");
+        assert_eq!(parse("Sugar: sweetness five"), "line 1: expected a property and a value, got sweetness five");
+        assert_eq!(parse("Sugar"), "line 1: expected an ingredient, got Sugar");
+    }
+
+    #[test]
+    fn test_constraint() {
+        assert_eq!("calories == 500".parse::<Constraint>(),
+                   Ok(Constraint { property: "calories".to_string(), comparison: Comparison::Equal, value: 500 }));
+        assert_eq!("texture>=-10".parse::<Constraint>(),
+                   Ok(Constraint { property: "texture".to_string(), comparison: Comparison::AtLeast, value: -10 }));
+        assert_eq!("calories = 500".parse::<Constraint>(), Err("invalid constraint calories = 500".to_string()));
+    }
+}
@@ -1,78 +1,58 @@
 extern crate utils;
 
 use std::env;
+use std::error;
+use std::fmt;
 use std::str::FromStr;
-use std::num::ParseIntError;
+use std::collections::HashMap;
 use std::io::{self, BufReader};
 use std::io::prelude::*;
 use std::fs::File;
 use utils::*;
 
-type Input = Vec<Sue>;
-
+// A component is interned the first time the input mentions it and refers to its name by index
 #[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
-enum Component {
-    Children, Cats, Samoyeds, Pomeranians, Akitas, Vizslas, Goldfish, Trees, Cars, Perfumes
-}
-
-impl Component {
-    fn resolve(s: &str) -> Component {
-        use Component::*;
-        match s {
-            "children"    => Children,
-            "cats"        => Cats,
-            "samoyeds"    => Samoyeds,
-            "pomeranians" => Pomeranians,
-            "akitas"      => Akitas,
-            "vizslas"     => Vizslas,
-            "goldfish"    => Goldfish,
-            "trees"       => Trees,
-            "cars"        => Cars,
-            "perfumes" => Perfumes,
-            _ => unreachable!()
-        }
-    }
+struct Component(usize);
+
+#[derive(Debug, Default)]
+struct Vocabulary {
+    names: Vec<String>,
+    components: HashMap<String, Component>
 }
 
-// TODO Simplify
-impl From<usize> for Component {
-    fn from(idx: usize) -> Self {
-        use Component::*;
-        match idx {
-            0 => Children,
-            1 => Cats,
-            2 => Samoyeds,
-            3 => Pomeranians,
-            4 => Akitas,
-            5 => Vizslas,
-            6 => Goldfish,
-            7 => Trees,
-            8 => Cars,
-            9 => Perfumes,
-            _ => unreachable!()
+impl Vocabulary {
+    fn intern(&mut self, name: &str) -> Component {
+        if let Some(c) = self.components.get(name) {
+            return *c;
         }
+        let c = Component(self.names.len());
+        self.names.push(name.to_string());
+        self.components.insert(name.to_string(), c);
+        c
     }
-}
 
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
-struct Components( [Option<i32>; 10] );
-
-impl Components {
-    fn new() -> Components {
-        Components( [None; 10] )
+    fn get(&self, name: &str) -> Option<Component> {
+        self.components.get(name).cloned()
     }
 }
 
+// The known amount of every component, grows to fit the components that are set
+#[derive(PartialEq, Eq, Hash, Clone, Debug, Default)]
+struct Components(Vec<Option<i32>>);
+
 impl std::ops::Index<Component> for Components  {
     type Output = Option<i32>;
     fn index(&self, c: Component) -> &Self::Output {
-        &self.0[c as usize]
+        self.0.get(c.0).unwrap_or(&None)
     }
 }
 
 impl std::ops::IndexMut<Component> for Components {
-    fn index_mut<'a>(&'a mut self, c: Component) -> &'a mut Self::Output {
-        &mut self.0[c as usize]
+    fn index_mut(&mut self, c: Component) -> &mut Self::Output {
+        if self.0.len() <= c.0 {
+            self.0.resize(c.0 + 1, None);
+        }
+        &mut self.0[c.0]
     }
 }
 
@@ -83,100 +63,348 @@ struct Sue {
 }
 
 impl Sue {
-    fn alike_fns(&self, other: &Sue, match_fns: &Vec<&ComponentMatchFn>) -> Vec<i32> {
-        let mut matches = vec![0; match_fns.len()];
+    fn parse(s: &str, vocabulary: &mut Vocabulary) -> Result<Sue, String> {
+        let (n, c) = s.split_once(':').ok_or_else(|| format!("expected Sue and her components, got {}", s))?;
+        let nr = n.strip_prefix("Sue ").and_then(|n| n.trim().parse().ok()).ok_or_else(|| format!("invalid Sue {}", n))?;
+        let mut components = Components::default();
+        for p in c.split(',') {
+            let (c, v) = p.split_once(':').ok_or_else(|| format!("expected a component and an amount, got {}", p.trim()))?;
+            let c = vocabulary.intern(c.trim());
+            components[c] = Some(v.trim().parse::<i32>().map_err(|_| format!("invalid amount {}", v.trim()))?);
+        }
+        Ok(Sue { nr, components })
+    }
+}
 
-        for i in 0..self.components.0.len() {
-            let c: Component = i.into();
+#[derive(Debug, Default)]
+struct Input {
+    vocabulary: Vocabulary,
+    sues: Vec<Sue>
+}
 
-            for i2 in 0..match_fns.len() {
-                if match_fns[i2](c, self.components.0[i], other.components.0[i]) {
-                    matches[i2] += 1;
-                }
+impl Input {
+    fn parse(lines: impl IntoIterator<Item = String>) -> Result<Input, ParseError> {
+        let mut input = Input::default();
+        for (i, l) in lines.into_iter().enumerate() {
+            if !l.trim().is_empty() {
+                let sue = Sue::parse(l.trim(), &mut input.vocabulary).map_err(|message| ParseError { line: i + 1, message })?;
+                input.sues.push(sue);
             }
         }
+        Ok(input)
+    }
+}
+
+// What the MFCSAM read from the gift
+const READING: &str =
+   "children = 3
+    cats = 7
+    samoyeds = 2
+    pomeranians = 3
+    akitas = 0
+    vizslas = 0
+    goldfish = 5
+    trees = 3
+    cars = 2
+    perfumes = 1";
 
-        matches
+// The same reading taking the outdated retroencabulator into account
+const RETROENCABULATOR_READING: &str =
+   "children = 3
+    cats > 7
+    samoyeds = 2
+    pomeranians < 3
+    akitas = 0
+    vizslas = 0
+    goldfish < 5
+    trees > 3
+    cars = 2
+    perfumes = 1";
+
+// What the amount of a component has to be, a range includes its start but not its end unless
+// written as `a..=b`
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Condition {
+    Equal(i32),
+    Greater(i32),
+    Less(i32),
+    AtLeast(i32),
+    AtMost(i32),
+    In(i32, i32)
+}
+
+impl Condition {
+    fn holds(&self, amount: i32) -> bool {
+        match *self {
+            Condition::Equal(n) => amount == n,
+            Condition::Greater(n) => amount > n,
+            Condition::Less(n) => amount < n,
+            Condition::AtLeast(n) => amount >= n,
+            Condition::AtMost(n) => amount <= n,
+            Condition::In(start, end) => start <= amount && amount < end
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct ParseError {
+    line: usize,
+    message: String
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
     }
 }
 
-impl FromStr for Sue {
-    type Err = ParseIntError;
+impl error::Error for ParseError {}
+
+// A condition per component, one per line like `cats > 7`, `akitas = 0` or `trees in 2..5`
+#[derive(Debug, PartialEq)]
+struct Query(Vec<(String, Condition)>);
+
+impl FromStr for Query {
+    type Err = ParseError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (n, c) = s.split_at(s.find(':').unwrap());
-        let c = &c[2..];
-        let mut components = Components::new();
-        for p in c.split(',') {
-            let (c, v) = p.split_at(p.find(':').unwrap());
-            let c = Component::resolve(c.trim());
-            let v = v[1..].trim().parse::<i32>()?;
-            components[c] = Some(v);
+        let mut conditions = vec![];
+        for (i, l) in s.lines().enumerate() {
+            let error = |message: String| ParseError { line: i + 1, message };
+            let parts: Vec<&str> = l.split_whitespace().collect();
+            if parts.is_empty() {
+                continue;
+            }
+            if parts.len() != 3 {
+                return Err(error(format!("expected a component, an operator and an amount, got {}", l.trim())));
+            }
+            let component = parts[0].to_string();
+            let amount = |s: &str| s.parse::<i32>().map_err(|_| error(format!("invalid amount {}", s)));
+            let condition = match parts[1] {
+                "=" | "==" => Condition::Equal(amount(parts[2])?),
+                ">" => Condition::Greater(amount(parts[2])?),
+                "<" => Condition::Less(amount(parts[2])?),
+                ">=" => Condition::AtLeast(amount(parts[2])?),
+                "<=" => Condition::AtMost(amount(parts[2])?),
+                "in" => {
+                    let (start, end) = parts[2].split_at(parts[2].find("..").ok_or_else(|| error(format!("invalid range {}", parts[2])))?);
+                    let (start, end) = match end.strip_prefix("..=") {
+                        Some(end) => (amount(start)?, amount(end)?.checked_add(1).ok_or_else(|| error(format!("range end {} is too large", end)))?),
+                        None => (amount(start)?, amount(&end[2..])?)
+                    };
+                    if start >= end {
+                        return Err(error(format!("empty range {}", parts[2])));
+                    }
+                    Condition::In(start, end)
+                },
+                op => return Err(error(format!("unknown operator {}", op)))
+            };
+            if conditions.iter().any(|(c, _)| *c == component) {
+                return Err(error(format!("{} is given more than once", parts[0])));
+            }
+            conditions.push((component, condition));
         }
-        Ok(Sue {
-            nr: n[4..].parse()?,
-            components: components
-        })
+        Ok(Query(conditions))
     }
 }
 
-fn real_sue() -> Sue {
-    "Sue 0:
-     children: 3,
-     cats: 7,
-     samoyeds: 2,
-     pomeranians: 3,
-     akitas: 0,
-     vizslas: 0,
-     goldfish: 5,
-     trees: 3,
-     cars: 2,
-     perfumes: 1".parse::<Sue>().unwrap()
+// How well what's known about a Sue fits the query, components she has no record of neither
+// match nor contradict it, which goes for every Sue when no one mentions the component
+#[derive(Debug, PartialEq)]
+struct Candidate {
+    nr: i32,
+    matching: usize,
+    contradicting: usize
 }
 
-type ComponentMatchFn = Fn(Component, Option<i32>, Option<i32>) -> bool;
+// Every Sue, the best match first: most matching components, then fewest contradicting
+fn rank(input: &Input, query: &Query) -> Vec<Candidate> {
+    let conditions: Vec<(Component, Condition)> = query.0.iter()
+        .filter_map(|(name, condition)| input.vocabulary.get(name).map(|c| (c, *condition)))
+        .collect();
+    let mut candidates: Vec<Candidate> = input.sues.iter()
+        .map(|sue| {
+            let known: Vec<bool> = conditions.iter()
+                .filter_map(|(c, condition)| sue.components[*c].map(|amount| condition.holds(amount)))
+                .collect();
+            Candidate {
+                nr: sue.nr,
+                matching: known.iter().filter(|m| **m).count(),
+                contradicting: known.iter().filter(|m| !**m).count()
+            }
+        })
+        .collect();
+    candidates.sort_by(|a, b| b.matching.cmp(&a.matching)
+        .then(a.contradicting.cmp(&b.contradicting))
+        .then(a.nr.cmp(&b.nr)));
+    candidates
+}
 
-fn match_part1(_c: Component, a: Option<i32>, b: Option<i32>) -> bool {
-    a == b
+// The candidates that share the best score
+fn best(candidates: &[Candidate]) -> &[Candidate] {
+    let tied = candidates.iter()
+        .take_while(|c| c.matching == candidates[0].matching && c.contradicting == candidates[0].contradicting)
+        .count();
+    &candidates[..tied]
 }
 
-fn match_part2(c: Component, a: Option<i32>, b: Option<i32>) -> bool {
-    use Component::*;
-    match (a, b) {
-        (Some(a), Some(b)) if c == Cats || c == Trees => a > b,
-        (Some(a), Some(b)) if c == Pomeranians || c == Goldfish => a < b,
-        (Some(a), Some(b)) => a == b,
-        _ => false
+fn format_best(candidates: &[Candidate]) -> String {
+    let best = best(candidates);
+    let nrs: Vec<_> = best.iter().map(|c| c.nr.to_string()).collect();
+    if best.len() > 1 {
+        format!("{} (tied)", nrs.join(", "))
+    } else {
+        nrs.join(", ")
     }
 }
 
-fn find_sue(input: &Input) -> (i32, i32) {
-    let real_sue = real_sue();
-    let match_fns: Vec<&ComponentMatchFn> = vec![&match_part1, &match_part2];
-
-    let ((p1, _), (p2, _)) = input.iter()
-        .fold(((0, 0), (0, 0)), |((mnr1, mal1), (mnr2, mal2)), sue| {
-            let alike = sue.alike_fns(&real_sue, &match_fns);
+// The first n candidates where tied candidates share a rank
+fn format_ranking(candidates: &[Candidate], n: usize) -> String {
+    let mut lines = vec![];
+    let mut rank = 0;
+    for (i, c) in candidates.iter().take(n).enumerate() {
+        if i == 0 || (c.matching, c.contradicting) != (candidates[i - 1].matching, candidates[i - 1].contradicting) {
+            rank = i + 1;
+        }
+        lines.push(format!("{:>3}. Sue {}: {} matching, {} contradicting", rank, c.nr, c.matching, c.contradicting));
+    }
+    lines.join("\n")
+}
 
-            (
-                if alike[0] > mal1 { (sue.nr, alike[0]) } else { (mnr1, mal1) },
-                if alike[1] > mal2 { (sue.nr, alike[1]) } else { (mnr2, mal2) }
-            )
-        });
+struct Options {
+    input: String,
+    query: Option<String>,
+    top: usize
+}
 
-    (p1, p2)
+impl Options {
+    fn parse<I: Iterator<Item=String>>(mut args: I) -> Options {
+        let mut input = None;
+        let mut query = None;
+        let mut top = 10;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--query" => query = Some(args.next().expect("No query file given")),
+                "--top" => top = args.next().and_then(|n| n.parse().ok()).expect("Invalid number of candidates"),
+                _ => input = Some(arg)
+            }
+        }
+        Options { input: input.expect("No input file given"), query, top }
+    }
 }
 
 fn main() {
     measure(|| {
-        let input = input().expect("Input failed");
-        let (part1, part2) = find_sue(&input);
-        println!("Part1: {}", part1);
-        println!("Part2: {}", part2);
+        let options = Options::parse(env::args().skip(1));
+        let input = input(&options.input).expect("Input failed");
+        match options.query.as_ref() {
+            Some(path) => {
+                let query = read_query(path).expect("Query failed");
+                println!("{}", format_ranking(&rank(&input, &query), options.top));
+            },
+            None => {
+                println!("Part1: {}", format_best(&rank(&input, &READING.parse().unwrap())));
+                println!("Part2: {}", format_best(&rank(&input, &RETROENCABULATOR_READING.parse().unwrap())));
+            }
+        }
     });
 }
 
-fn input() -> io::Result<Input> {
-    let f = File::open(env::args().skip(1).next().expect("No input file given"))?;
+fn read_query(path: &str) -> io::Result<Query> {
+    let mut s = String::new();
+    File::open(path)?.read_to_string(&mut s)?;
+    s.parse().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn input(path: &str) -> io::Result<Input> {
+    let f = File::open(path)?;
     let f = BufReader::new(f);
-    Ok(f.lines().map(|l| l.unwrap().parse::<Sue>().unwrap()).collect())
+    let lines = f.lines().collect::<io::Result<Vec<_>>>()?;
+    Input::parse(lines).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &'static str =
+       "Sue 1: goldfish: 6, trees: 9, akitas: 0
+        Sue 2: goldfish: 7, trees: 1, akitas: 0
+        Sue 3: cats: 7, akitas: 0, perfumes: 1
+        Sue 4: cats: 8, trees: 4, goldfish: 4
+        Sue 5: cats: 7, cars: 2, vizslas: 0";
+
+    fn as_input(s: &str) -> Input {
+        Input::parse(s.split('\n').map(|s| s.trim().into())).unwrap()
+    }
+
+    #[test]
+    fn test_readings() {
+        let input = as_input(INPUT);
+        assert_eq!(format_best(&rank(&input, &READING.parse().unwrap())), "3, 5 (tied)");
+        assert_eq!(format_best(&rank(&input, &RETROENCABULATOR_READING.parse().unwrap())), "4");
+    }
+
+    #[test]
+    fn test_rank() {
+        let input = as_input(INPUT);
+        let query: Query = "trees in 2..=9\ngoldfish < 7".parse().unwrap();
+        let candidates = rank(&input, &query);
+        assert_eq!(candidates[0], Candidate { nr: 1, matching: 2, contradicting: 0 });
+        assert_eq!(candidates[1], Candidate { nr: 4, matching: 2, contradicting: 0 });
+        assert_eq!(candidates[2], Candidate { nr: 3, matching: 0, contradicting: 0 });
+        assert_eq!(candidates[4], Candidate { nr: 2, matching: 0, contradicting: 2 });
+        assert_eq!(format_ranking(&candidates, 3),
+                   "  1. Sue 1: 2 matching, 0 contradicting\n  \
+                      1. Sue 4: 2 matching, 0 contradicting\n  \
+                      3. Sue 3: 0 matching, 0 contradicting");
+    }
+
+    #[test]
+    fn test_query() {
+        assert_eq!("cats > 7\nakitas = 0\n\ntrees in 2..5".parse::<Query>(), Ok(Query(vec![
+            ("cats".to_string(), Condition::Greater(7)),
+            ("akitas".to_string(), Condition::Equal(0)),
+            ("trees".to_string(), Condition::In(2, 5))
+        ])));
+        assert!(Condition::In(2, 5).holds(2));
+        assert!(!Condition::In(2, 5).holds(5));
+        assert!(Condition::AtMost(5).holds(5));
+
+        let error = |s: &str| s.parse::<Query>().unwrap_err().to_string();
+        assert_eq!(error("cats != 7"), "line 1: unknown operator !=");
+        assert_eq!(error("cats > seven"), "line 1: invalid amount seven");
+        assert_eq!(error("trees in 2-5"), "line 1: invalid range 2-5");
+        assert_eq!(error("cats > 7\ntrees in 5..2"), "line 2: empty range 5..2");
+        assert_eq!(error("trees in 5..5"), "line 1: empty range 5..5");
+        assert_eq!(error("trees in 5..=4"), "line 1: empty range 5..=4");
+        assert_eq!(error("trees in 2..=2147483647"), "line 1: range end 2147483647 is too large");
+        assert_eq!(error("trees in"), "line 1: expected a component, an operator and an amount, got trees in");
+        assert_eq!(error("cats > 7\ncats < 9"), "line 2: cats is given more than once");
+    }
+
+    #[test]
+    fn test_any_component() {
+        let input = as_input(
+           "Sue 1: dogs: 2, cats: 1
+            Sue 2: parrots: 3, dogs: 5
+            Sue 3: cats: 1");
+        assert_eq!(input.vocabulary.names, vec!["dogs", "cats", "parrots"]);
+        assert_eq!(input.sues[2].components[input.vocabulary.get("cats").unwrap()], Some(1));
+        assert_eq!(input.sues[2].components[input.vocabulary.get("parrots").unwrap()], None);
+
+        // Nobody has unicorns so they count for no one
+        let query: Query = "dogs > 3\nparrots = 3\nunicorns = 1".parse().unwrap();
+        let candidates = rank(&input, &query);
+        assert_eq!(candidates[0], Candidate { nr: 2, matching: 2, contradicting: 0 });
+        assert_eq!(candidates[2], Candidate { nr: 1, matching: 0, contradicting: 1 });
+    }
+
+    #[test]
+    fn test_input_errors() {
+        let error = |s: &str| Input::parse(s.split('\n').map(|s| s.into())).unwrap_err().to_string();
+        assert_eq!(error("Sue 1: cats: 1\nSue 2 cats 1"), "line 2: expected Sue and her components, got Sue 2 cats 1");
+        assert_eq!(error("Susan 1: cats: 1"), "line 1: invalid Sue Susan 1");
+        assert_eq!(error("Sue 1: cats 1"), "line 1: expected a component and an amount, got cats 1");
+        assert_eq!(error("Sue 1: cats: many"), "line 1: invalid amount many");
+    }
 }
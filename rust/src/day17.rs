@@ -9,9 +9,9 @@ use std::io::prelude::*;
 use std::fs::File;
 use utils::*;
 
-type Input = Vec<i32>;
+type Input = Vec<usize>;
 
-fn solve(input: &Input, expect_liters: i32) -> (usize, usize) {
+fn solve(input: &Input, expect_liters: usize) -> (usize, usize) {
     let mut found_containers = HashSet::new();
     let mut queue = VecDeque::from_iter((0..input.len()).map(|i| (Vec::from_iter(i..=i), input[i])));
 
@@ -48,19 +48,168 @@ fn solve(input: &Input, expect_liters: i32) -> (usize, usize) {
     (found_containers.len(), n_least)
 }
 
+// The number of combinations of containers that hold exactly the liters, by how many containers
+// they use. Goes through the containers once keeping the ways to fill every amount up to the
+// liters with every number of containers, O(n * liters) for each number of containers.
+fn count_by_containers(input: &[usize], liters: usize) -> Vec<BigUint> {
+    let n = input.len();
+    let mut ways = vec![vec![BigUint::zero(); liters + 1]; n + 1];
+    ways[0][0] = BigUint::from(1);
+    for (c, &size) in input.iter().enumerate() {
+        if size > liters {
+            continue;
+        }
+        // Backwards so that each container is used at most once
+        for k in (1..=c + 1).rev() {
+            let (fewer, more) = ways.split_at_mut(k);
+            let (from, to) = (&fewer[k - 1], &mut more[0]);
+            for amount in (size..=liters).rev() {
+                if !from[amount - size].is_zero() {
+                    to[amount] += &from[amount - size];
+                }
+            }
+        }
+    }
+    ways.into_iter().map(|mut w| w.swap_remove(liters)).collect()
+}
+
+// All combinations and the number of combinations with the fewest containers, along with how
+// many containers that is
+fn count(counts: &[BigUint]) -> (BigUint, Option<(usize, BigUint)>) {
+    let mut all = BigUint::zero();
+    for n in counts {
+        all += n;
+    }
+    let fewest = counts.iter().enumerate().find(|(_, n)| !n.is_zero()).map(|(k, n)| (k, n.clone()));
+    (all, fewest)
+}
+
+// Lazily goes through the combinations of containers that hold exactly the liters, as indexes
+// into the containers in increasing order. Only containers after which the rest can still be
+// made up exactly are chosen, so no time goes into dead ends.
+struct Fills<'a> {
+    containers: &'a [usize],
+    liters: usize,
+    // Whether the containers from an index on can hold exactly a number of liters
+    reachable: Vec<Vec<bool>>,
+    chosen: Vec<usize>,
+    filled: usize,
+    next: usize,
+    first: bool
+}
+
+impl<'a> Fills<'a> {
+    fn new(containers: &'a [usize], liters: usize) -> Fills<'a> {
+        let n = containers.len();
+        let mut reachable = vec![vec![false; liters + 1]; n + 1];
+        reachable[n][0] = true;
+        for i in (0..n).rev() {
+            for amount in 0..=liters {
+                reachable[i][amount] = reachable[i + 1][amount]
+                    || (containers[i] <= amount && reachable[i + 1][amount - containers[i]]);
+            }
+        }
+        Fills { containers, liters, reachable, chosen: vec![], filled: 0, next: 0, first: true }
+    }
+}
+
+impl<'a> Iterator for Fills<'a> {
+    type Item = Vec<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // No containers at all hold nothing
+        if self.first {
+            self.first = false;
+            if self.liters == 0 {
+                return Some(vec![]);
+            }
+        }
+
+        loop {
+            let rest = self.liters - self.filled;
+            if self.next < self.containers.len() && self.reachable[self.next][rest] {
+                let i = self.next;
+                self.next += 1;
+                let size = self.containers[i];
+                if size <= rest && self.reachable[i + 1][rest - size] {
+                    self.chosen.push(i);
+                    self.filled += size;
+                    if self.filled == self.liters {
+                        return Some(self.chosen.clone());
+                    }
+                }
+            } else {
+                let i = self.chosen.pop()?;
+                self.filled -= self.containers[i];
+                self.next = i + 1;
+            }
+        }
+    }
+}
+
+struct Options {
+    input: String,
+    liters: usize,
+    show: usize,
+    by_count: bool,
+    reference: bool
+}
+
+impl Options {
+    fn parse<I: Iterator<Item=String>>(mut args: I) -> Options {
+        let mut input = None;
+        let mut liters = 150;
+        let mut show = 0;
+        let mut by_count = false;
+        let mut reference = false;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--liters" => liters = args.next().and_then(|n| n.parse().ok()).expect("Invalid number of liters"),
+                "--show" => show = args.next().and_then(|n| n.parse().ok()).expect("Invalid number of combinations"),
+                "--by-count" => by_count = true,
+                "--reference" => reference = true,
+                _ => input = Some(arg)
+            }
+        }
+        Options { input: input.expect("No input file given"), liters, show, by_count, reference }
+    }
+}
+
 fn main() {
     measure(|| {
-        let input = input().expect("Input failed");
-        let (part1, part2) = solve(&input, 150);
-        println!("Part1: {}", part1);
-        println!("Part2: {}", part2);
+        let options = Options::parse(env::args().skip(1));
+        let input = input(&options.input).expect("Input failed");
+        if options.reference {
+            let (part1, part2) = solve(&input, options.liters);
+            println!("Part1: {}", part1);
+            println!("Part2: {}", part2);
+        } else {
+            let counts = count_by_containers(&input, options.liters);
+            let (part1, part2) = count(&counts);
+            println!("Part1: {}", part1);
+            match part2 {
+                Some((containers, n)) => println!("Part2: {} ({} containers)", n, containers),
+                None => println!("Part2: 0")
+            }
+            if options.by_count {
+                for (containers, n) in counts.iter().enumerate().filter(|(_, n)| !n.is_zero()) {
+                    println!("{} containers: {}", containers, n);
+                }
+            }
+        }
+        if options.show > 0 {
+            for fill in Fills::new(&input, options.liters).take(options.show) {
+                let sizes: Vec<_> = fill.iter().map(|i| input[*i].to_string()).collect();
+                println!("{}", sizes.join(" + "));
+            }
+        }
     });
 }
 
-fn input() -> io::Result<Input> {
-    let f = File::open(env::args().skip(1).next().expect("No input file given"))?;
+fn input(path: &str) -> io::Result<Input> {
+    let f = File::open(path)?;
     let f = BufReader::new(f);
-    Ok(f.lines().map(|l| l.unwrap().parse::<i32>().unwrap()).collect())
+    Ok(f.lines().map(|l| l.unwrap().parse::<usize>().unwrap()).collect())
 }
 
 #[cfg(test)]
@@ -75,7 +224,7 @@ mod tests {
         5";
 
     fn as_input(s: &str) -> Input {
-        s.split('\n').map(|s| s.trim().parse::<i32>().unwrap()).collect()
+        s.split('\n').map(|s| s.trim().parse::<usize>().unwrap()).collect()
     }
 
     #[test]
@@ -87,4 +236,79 @@ mod tests {
     fn test_part2() {
         assert_eq!(solve(&as_input(INPUT), 25).1, 3);
     }
+
+    fn big(n: usize) -> BigUint {
+        BigUint::from(n as u64)
+    }
+
+    fn count_all(input: &[usize], liters: usize) -> (BigUint, Option<(usize, BigUint)>) {
+        count(&count_by_containers(input, liters))
+    }
+
+    #[test]
+    fn test_count_by_containers() {
+        let input = as_input(INPUT);
+        assert_eq!(count_by_containers(&input, 25), vec![big(0), big(0), big(3), big(1), big(0), big(0)]);
+        assert_eq!(count_by_containers(&input, 0), vec![big(1), big(0), big(0), big(0), big(0), big(0)]);
+        assert_eq!(count_by_containers(&[], 0), vec![big(1)]);
+        assert_eq!(count_by_containers(&[0, 5, 0], 5), vec![big(0), big(1), big(2), big(1)]);
+        // C(150, k) ways to pick any k of 150 one liter containers for k liters
+        assert_eq!(count_by_containers(&vec![1; 150], 3)[3], big(551300));
+    }
+
+    #[test]
+    fn test_count() {
+        let input = as_input(INPUT);
+        assert_eq!(count_all(&input, 25), (big(4), Some((2, big(3)))));
+        assert_eq!(count_all(&input, 20), (big(4), Some((1, big(1)))));
+        assert_eq!(count_all(&input, 100), (big(0), None));
+        assert_eq!(count_all(&input, 0), (big(1), Some((0, big(1)))));
+        assert_eq!(count_all(&[0, 5, 0], 5), (big(4), Some((1, big(1)))));
+    }
+
+    #[test]
+    fn test_fills() {
+        let input = as_input(INPUT);
+        assert_eq!(Fills::new(&input, 25).collect::<Vec<_>>(), vec![
+            vec![0, 3],
+            vec![0, 4],
+            vec![1, 2],
+            vec![1, 3, 4]
+        ]);
+        assert_eq!(Fills::new(&input, 0).collect::<Vec<_>>(), vec![vec![]]);
+        assert_eq!(Fills::new(&input, 100).next(), None);
+        assert_eq!(Fills::new(&[0, 5, 0], 5).count(), 4);
+    }
+
+    #[test]
+    fn test_matches_solve() {
+        let mut rng = Lcg::new(17);
+        for n in 1..12 {
+            let input: Input = (0..n).map(|_| rng.between(1, 20) as usize).collect();
+            for liters in [10, 25, 40] {
+                let (all, fewest) = count_all(&input, liters);
+                let fills: Vec<_> = Fills::new(&input, liters).collect();
+                assert_eq!(all, big(fills.len()));
+                assert!(fills.iter().all(|f| f.iter().map(|i| input[*i]).sum::<usize>() == liters));
+                // The reference never counts a single container holding everything
+                if !all.is_zero() && !input.contains(&liters) {
+                    let (part1, part2) = solve(&input, liters);
+                    assert_eq!((all, fewest.unwrap().1), (big(part1), big(part2)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_many_containers() {
+        // Any 75 of 150 one liter containers, C(150, 75) is beyond u128
+        let all: BigUint = "92826069736708789698985814872605121940117520".parse().unwrap();
+        assert_eq!(count_all(&vec![1; 150], 75), (all.clone(), Some((75, all))));
+
+        let input: Input = (1..=100).collect();
+        assert_eq!(count_all(&input, 2525).1.map(|(containers, _)| containers), Some(30));
+        let fills: Vec<_> = Fills::new(&input, 2525).take(1000).collect();
+        assert_eq!(fills.len(), 1000);
+        assert!(fills.iter().all(|f| f.iter().map(|i| input[*i]).sum::<usize>() == 2525));
+    }
 }
\ No newline at end of file
@@ -1,7 +1,10 @@
 extern crate utils;
 
-use std::env;
 use std::cmp;
+use std::env;
+use std::error;
+use std::fmt;
+use std::str::FromStr;
 use std::collections::VecDeque;
 use std::io::{self, BufReader};
 use std::io::prelude::*;
@@ -10,34 +13,50 @@ use utils::*;
 
 type Input = LightGrid;
 
-#[cfg(test)]
-const GRID_SIZE: usize = 6;
-#[cfg(not(test))]
-const GRID_SIZE: usize = 100;
-
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 struct LightGrid {
-    lights: [[bool; GRID_SIZE]; GRID_SIZE]
+    width: usize,
+    height: usize,
+    lights: Vec<Vec<bool>>
 }
 
 impl LightGrid {
-    fn read<R: Read>(reader: BufReader<R>) -> LightGrid {
-        reader.lines().map(|l| l.unwrap().trim().to_string()).enumerate()
-            .fold(LightGrid { lights: [[false; GRID_SIZE]; GRID_SIZE] }, |mut lg, (y, l)| {
-                for (x, c) in l.chars().enumerate() {
-                    lg.lights[y][x] = c == '#';
+    fn parse(lines: impl IntoIterator<Item = String>) -> Result<LightGrid, GridError> {
+        let mut lights: Vec<Vec<bool>> = vec![];
+        for (i, l) in lines.into_iter().enumerate() {
+            let error = |message: String| GridError { line: i + 1, message };
+            let l = l.trim();
+            if l.is_empty() {
+                continue;
+            }
+            let row = l.chars().map(|c| match c {
+                '#' => Ok(true),
+                '.' => Ok(false),
+                _ => Err(error(format!("unknown light {:?}", c)))
+            }).collect::<Result<Vec<_>, _>>()?;
+            if let Some(first) = lights.first() {
+                if row.len() != first.len() {
+                    return Err(error(format!("{} lights where the grid is {} wide", row.len(), first.len())));
                 }
-                lg
-            })
+            }
+            lights.push(row);
+        }
+        if lights.is_empty() {
+            return Err(GridError { line: 0, message: "no lights".to_string() });
+        }
+        Ok(LightGrid { width: lights[0].len(), height: lights.len(), lights })
     }
 
-    fn n_adjacent_on(&self, x: usize, y: usize) -> usize {
-        let x = x as i32;
-        let y = y as i32;
+    fn corners(&self) -> Vec<(usize, usize)> {
+        vec![(0, 0), (self.width - 1, 0), (0, self.height - 1), (self.width - 1, self.height - 1)]
+    }
+
+    // The lights around x, y that are on given the columns and rows around it, which include x and y
+    fn n_adjacent_on(&self, x: usize, y: usize, columns: &[usize], rows: &[usize]) -> usize {
         let mut on_cnt = 0;
-        for ay in cmp::max(0, y - 1)..cmp::min(GRID_SIZE as i32, y + 2) {
-            for ax in cmp::max(0, x as i32 - 1)..cmp::min(GRID_SIZE as i32, x + 2) {
-                if self.lights[ay as usize][ax as usize] && !(ax == x && ay == y) {
+        for &ay in rows {
+            for &ax in columns {
+                if (ax, ay) != (x, y) && self.lights[ay][ax] {
                     on_cnt += 1;
                 }
             }
@@ -45,40 +64,32 @@ impl LightGrid {
         on_cnt
     }
 
-    fn step(&mut self, last: &LightGrid) {
-        for y in 0..GRID_SIZE {
-            for x in 0..GRID_SIZE {
-                let n_adj_on = last.n_adjacent_on(x, y);
-                self.lights[y][x] = last.lights[y][x] && (n_adj_on == 2 || n_adj_on == 3)
-                                    || !last.lights[y][x] && n_adj_on == 3;
+    fn step(&mut self, last: &LightGrid, rule: &Rule, edges: Edges) {
+        let columns: Vec<Vec<usize>> = (0..self.width).map(|x| edges.around(x, self.width)).collect();
+        let rows: Vec<Vec<usize>> = (0..self.height).map(|y| edges.around(y, self.height)).collect();
+        for (y, around_y) in rows.iter().enumerate() {
+            for (x, around_x) in columns.iter().enumerate() {
+                let n_adj_on = last.n_adjacent_on(x, y, around_x, around_y);
+                self.lights[y][x] = if last.lights[y][x] { rule.survival[n_adj_on] } else { rule.birth[n_adj_on] };
             }
         }
     }
 
-    fn n_on(&self) -> i32 {
-        let mut on = 0;
-        for y in 0..GRID_SIZE {
-            for x in 0..GRID_SIZE {
-                if self.lights[y][x] {
-                    on += 1;
-                }
-            }
-        }
-        on
+    fn n_on(&self) -> usize {
+        self.lights.iter().map(|row| row.iter().filter(|on| **on).count()).sum()
     }
 
-    fn turn_on_stuck(&mut self) {
-        self.lights[0][0] = true;
-        self.lights[GRID_SIZE - 1][0] = true;
-        self.lights[0][GRID_SIZE - 1] = true;
-        self.lights[GRID_SIZE - 1][GRID_SIZE - 1] = true;
+    fn turn_on_stuck(&mut self, stuck: &[(usize, usize)]) {
+        for &(x, y) in stuck {
+            self.lights[y][x] = true;
+        }
     }
 
     #[cfg(feature = "print")]
     fn print(&self) {
-        for y in 0..GRID_SIZE {
-            for x in 0..GRID_SIZE {
-                print!("{}", if self.lights[y][x] { '#' } else { '.' });
+        for row in &self.lights {
+            for on in row {
+                print!("{}", if *on { '#' } else { '.' });
             }
             println!();
         }
@@ -86,31 +97,213 @@ impl LightGrid {
     }
 }
 
-fn simulate(input: &Input, steps: usize, turn_on_stuck: bool) -> i32 {
-    #[cfg(feature = "print")] {
-        println!("\nInitial");
-        input.print();
+#[derive(Debug, PartialEq)]
+struct GridError {
+    line: usize,
+    message: String
+}
+
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl error::Error for GridError {}
+
+// Which numbers of neighbours that are on turn a light on (birth) or keep it on (survival)
+#[derive(Clone, Debug, PartialEq)]
+struct Rule {
+    birth: [bool; 9],
+    survival: [bool; 9]
+}
+
+// Well known rules that can be given by name instead
+const NAMED_RULES: [(&str, &str); 3] = [
+    ("life", "B3/S23"),
+    ("highlife", "B36/S23"),
+    ("seeds", "B2/S")
+];
+
+impl Default for Rule {
+    fn default() -> Rule {
+        "B3/S23".parse().unwrap()
+    }
+}
+
+impl FromStr for Rule {
+    type Err = RuleError;
+
+    // Either a name or the B/S notation like `B36/S23`, where `B2/S` never lets a light stay on
+    fn from_str(s: &str) -> Result<Rule, RuleError> {
+        let s = s.trim();
+        if let Some((_, rule)) = NAMED_RULES.iter().find(|(name, _)| name.eq_ignore_ascii_case(s)) {
+            return rule.parse();
+        }
+
+        let (birth, survival) = s.split_once('/').ok_or_else(|| RuleError::Format(s.to_string()))?;
+        let neighbours = |part: &str, prefix: char| -> Result<[bool; 9], RuleError> {
+            let mut counts = [false; 9];
+            let digits = part.strip_prefix(prefix).or_else(|| part.strip_prefix(prefix.to_ascii_lowercase()))
+                .ok_or_else(|| RuleError::Format(s.to_string()))?;
+            for c in digits.chars() {
+                match c.to_digit(10) {
+                    Some(n) if n <= 8 && !counts[n as usize] => counts[n as usize] = true,
+                    Some(n) if n <= 8 => return Err(RuleError::Repeated(c)),
+                    _ => return Err(RuleError::Neighbours(c))
+                }
+            }
+            Ok(counts)
+        };
+        Ok(Rule { birth: neighbours(birth, 'B')?, survival: neighbours(survival, 'S')? })
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let digits = |counts: &[bool; 9]| (0..9).filter(|n| counts[*n]).map(|n| n.to_string()).collect::<String>();
+        write!(f, "B{}/S{}", digits(&self.birth), digits(&self.survival))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum RuleError {
+    Format(String),
+    Neighbours(char),
+    Repeated(char)
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuleError::Format(rule) => write!(f, "{} is neither a known rule nor like B3/S23", rule),
+            RuleError::Neighbours(c) => write!(f, "{} is not a number of neighbours from 0 to 8", c),
+            RuleError::Repeated(c) => write!(f, "{} neighbours is given twice", c)
+        }
+    }
+}
+
+impl error::Error for RuleError {}
+
+// Whether the lights at the edges see nothing beyond them or the lights at the opposite edge
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum Edges {
+    #[default]
+    Clipped,
+    Toroidal
+}
+
+impl Edges {
+    // The positions next to and at a position along a side of the given length. Wrapping around
+    // a side shorter than three meets the same positions again, those are only given once.
+    fn around(&self, at: usize, len: usize) -> Vec<usize> {
+        match self {
+            Edges::Clipped => (at.saturating_sub(1)..cmp::min(at + 2, len)).collect(),
+            Edges::Toroidal => {
+                let mut around = vec![at];
+                for wrapped in [(at + len - 1) % len, (at + 1) % len] {
+                    if !around.contains(&wrapped) {
+                        around.push(wrapped);
+                    }
+                }
+                around
+            }
+        }
+    }
+}
+
+impl FromStr for Edges {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Edges, String> {
+        match s {
+            "clipped" => Ok(Edges::Clipped),
+            "toroidal" => Ok(Edges::Toroidal),
+            _ => Err(format!("unknown edges {}, expected clipped or toroidal", s))
+        }
+    }
+}
+
+// Lights that are always on, either the corners of whatever grid or a single light
+#[derive(Clone, Debug, PartialEq)]
+enum Stuck {
+    Corners,
+    At(usize, usize)
+}
+
+impl FromStr for Stuck {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Stuck, String> {
+        if s == "corners" {
+            return Ok(Stuck::Corners);
+        }
+        s.split_once(',')
+            .and_then(|(x, y)| Some(Stuck::At(x.trim().parse().ok()?, y.trim().parse().ok()?)))
+            .ok_or_else(|| format!("unknown stuck light {}, expected corners or x,y", s))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct StuckError {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize
+}
+
+impl fmt::Display for StuckError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "stuck light {},{} is outside the {}x{} grid", self.x, self.y, self.width, self.height)
+    }
+}
+
+impl error::Error for StuckError {}
+
+#[derive(Clone, Debug, Default)]
+struct Life {
+    rule: Rule,
+    edges: Edges,
+    stuck: Vec<Stuck>
+}
+
+impl Life {
+    fn stuck_lights(&self, grid: &LightGrid) -> Result<Vec<(usize, usize)>, StuckError> {
+        let mut lights = vec![];
+        for stuck in &self.stuck {
+            match *stuck {
+                Stuck::Corners => lights.extend(grid.corners()),
+                Stuck::At(x, y) if x < grid.width && y < grid.height => lights.push((x, y)),
+                Stuck::At(x, y) => return Err(StuckError { x, y, width: grid.width, height: grid.height })
+            }
+        }
+        Ok(lights)
     }
+}
+
+fn simulate(input: &Input, steps: usize, life: &Life) -> Result<usize, StuckError> {
+    let stuck = life.stuck_lights(input)?;
 
     let mut grids: VecDeque<LightGrid> = VecDeque::with_capacity(2);
     grids.push_back((*input).clone());
     grids.push_back((*input).clone());
+    grids[0].turn_on_stuck(&stuck);
+
+    #[cfg(feature = "print")] {
+        println!("\nInitial");
+        grids[0].print();
+    }
 
-    for s in 0..steps {
-        let mut last_grid = grids.pop_front().unwrap();
+    for _s in 0..steps {
+        let last_grid = grids.pop_front().unwrap();
         let mut next_grid = grids.pop_front().unwrap();
 
-        if turn_on_stuck && s == 0 {
-            last_grid.turn_on_stuck();
-        }
-        next_grid.step(&last_grid);
-        if turn_on_stuck {
-            next_grid.turn_on_stuck();
-        }
+        next_grid.step(&last_grid, &life.rule, life.edges);
+        next_grid.turn_on_stuck(&stuck);
 
         #[cfg(feature = "print")] {
             println!();
-            println!("Step: {}", s + 1);
+            println!("Step: {}", _s + 1);
             next_grid.print();
         }
 
@@ -119,29 +312,65 @@ fn simulate(input: &Input, steps: usize, turn_on_stuck: bool) -> i32 {
     }
 
     let last_grid = grids.pop_front().unwrap();
-    last_grid.n_on()
+    Ok(last_grid.n_on())
+}
+
+fn part1(input: &Input, steps: usize, life: &Life) -> Result<usize, StuckError> {
+    simulate(input, steps, life)
+}
+
+// Like part 1 but with the corners stuck on as well
+fn part2(input: &Input, steps: usize, life: &Life) -> Result<usize, StuckError> {
+    let mut life = life.clone();
+    life.stuck.push(Stuck::Corners);
+    simulate(input, steps, &life)
+}
+
+fn format_on(on: Result<usize, StuckError>) -> String {
+    match on {
+        Ok(on) => on.to_string(),
+        Err(e) => e.to_string()
+    }
 }
 
-fn part1(input: &Input, steps: usize) -> i32 {
-    simulate(input, steps, false)
+struct Options {
+    input: String,
+    steps: usize,
+    life: Life
 }
 
-fn part2(input: &Input, steps: usize) -> i32 {
-    simulate(input, steps, true)
+impl Options {
+    fn parse<I: Iterator<Item=String>>(mut args: I) -> Options {
+        let mut input = None;
+        let mut steps = 100;
+        let mut life = Life::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--steps" => steps = args.next().and_then(|n| n.parse().ok()).expect("Invalid number of steps"),
+                "--rule" => life.rule = args.next().expect("No rule given").parse().unwrap_or_else(|e| panic!("{}", e)),
+                "--edges" => life.edges = args.next().expect("No edges given").parse().unwrap_or_else(|e| panic!("{}", e)),
+                "--stuck" => life.stuck.push(args.next().expect("No stuck light given").parse().unwrap_or_else(|e| panic!("{}", e))),
+                _ => input = Some(arg)
+            }
+        }
+        Options { input: input.expect("No input file given"), steps, life }
+    }
 }
 
 fn main() {
     measure(|| {
-        let input = input().expect("Input failed");
-        println!("Part1: {}", part1(&input, 100));
-        println!("Part2: {}", part2(&input, 100));
+        let options = Options::parse(env::args().skip(1));
+        let input = input(&options.input).expect("Input failed");
+        println!("Part1: {}", format_on(part1(&input, options.steps, &options.life)));
+        println!("Part2: {}", format_on(part2(&input, options.steps, &options.life)));
     });
 }
 
-fn input() -> io::Result<Input> {
-    let f = File::open(env::args().skip(1).next().expect("No input file given"))?;
+fn input(path: &str) -> io::Result<Input> {
+    let f = File::open(path)?;
     let f = BufReader::new(f);
-    Ok(LightGrid::read(f))
+    let lines = f.lines().collect::<io::Result<Vec<_>>>()?;
+    LightGrid::parse(lines).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
 }
 
 #[cfg(test)]
@@ -157,17 +386,119 @@ mod tests {
         ####..";
 
     fn as_input(s: &str) -> Input {
-        let f = BufReader::new(s.as_bytes());
-        LightGrid::read(f)
+        LightGrid::parse(s.lines().map(|l| l.to_string())).unwrap()
+    }
+
+    fn life(rule: &str, edges: Edges, stuck: Vec<Stuck>) -> Life {
+        Life { rule: rule.parse().unwrap(), edges, stuck }
+    }
+
+    fn step(grid: &LightGrid, life: &Life) -> LightGrid {
+        let mut next = grid.clone();
+        next.step(grid, &life.rule, life.edges);
+        next
     }
 
     #[test]
     fn test_part1() {
-        assert_eq!(part1(&as_input(INPUT), 4), 4);
+        assert_eq!(part1(&as_input(INPUT), 4, &Life::default()), Ok(4));
     }
 
     #[test]
     fn test_part2() {
-        assert_eq!(part2(&as_input(INPUT), 5), 17);
+        assert_eq!(part2(&as_input(INPUT), 5, &Life::default()), Ok(17));
+    }
+
+    #[test]
+    fn test_parse_grid() {
+        let grid = as_input("#..\n.#.");
+        assert_eq!((grid.width, grid.height, grid.n_on()), (3, 2, 2));
+        assert_eq!(grid.corners(), vec![(0, 0), (2, 0), (0, 1), (2, 1)]);
+        assert_eq!(LightGrid::parse(vec!["#..".to_string(), "#.".to_string()]).unwrap_err().line, 2);
+        assert_eq!(LightGrid::parse(vec!["#.o".to_string()]).unwrap_err().line, 1);
+        assert!(LightGrid::parse(vec![]).is_err());
+    }
+
+    #[test]
+    fn test_parse_rule() {
+        let rule: Rule = "B36/S23".parse().unwrap();
+        assert_eq!(rule.to_string(), "B36/S23");
+        assert_eq!(rule, "highlife".parse().unwrap());
+        assert_eq!("b2/s".parse::<Rule>().unwrap(), "Seeds".parse().unwrap());
+        assert_eq!(Rule::default(), "life".parse().unwrap());
+        assert_eq!("B3S23".parse::<Rule>(), Err(RuleError::Format("B3S23".to_string())));
+        assert_eq!("S23/B3".parse::<Rule>(), Err(RuleError::Format("S23/B3".to_string())));
+        assert_eq!("B39/S23".parse::<Rule>(), Err(RuleError::Neighbours('9')));
+        assert_eq!("B33/S23".parse::<Rule>(), Err(RuleError::Repeated('3')));
+    }
+
+    #[test]
+    fn test_highlife() {
+        // The middle light has six neighbours, which only turns it on in HighLife
+        let grid = as_input(
+           "###
+            ...
+            ###");
+        assert!(!step(&grid, &Life::default()).lights[1][1]);
+        assert!(step(&grid, &life("highlife", Edges::Clipped, vec![])).lights[1][1]);
+    }
+
+    #[test]
+    fn test_seeds() {
+        let grid = as_input(
+           "....
+            .##.
+            ....");
+        assert_eq!(step(&grid, &life("seeds", Edges::Clipped, vec![])), as_input(
+           ".##.
+            ....
+            .##."));
+    }
+
+    #[test]
+    fn test_toroidal() {
+        // A glider moves one light diagonally every four steps, so it is back after going round
+        let glider = as_input(
+           ".#...
+            ..#..
+            ###..
+            .....
+            .....");
+        let torus = life("life", Edges::Toroidal, vec![]);
+        let mut grid = glider.clone();
+        for _ in 0..20 {
+            grid = step(&grid, &torus);
+        }
+        assert_eq!(grid, glider);
+        assert_eq!(simulate(&glider, 20, &torus), Ok(5));
+        // Clipped, it ends up as a block in the corner
+        assert_eq!(simulate(&glider, 20, &Life::default()), Ok(4));
+    }
+
+    #[test]
+    fn test_small_torus() {
+        // Wrapping around a narrow grid doesn't see a light twice or a light itself
+        assert_eq!(step(&as_input("#"), &life("B/S8", Edges::Toroidal, vec![])), as_input("."));
+        assert_eq!(step(&as_input("#.\n.."), &life("B1/S1", Edges::Toroidal, vec![])), as_input(".#\n##"));
+        assert_eq!(Edges::Toroidal.around(0, 1), vec![0]);
+        assert_eq!(Edges::Toroidal.around(1, 2), vec![1, 0]);
+        assert_eq!(Edges::Toroidal.around(0, 5), vec![0, 4, 1]);
+        assert_eq!(Edges::Clipped.around(0, 5), vec![0, 1]);
+        assert_eq!(Edges::Clipped.around(4, 5), vec![3, 4]);
+    }
+
+    #[test]
+    fn test_stuck() {
+        let grid = as_input(
+           "...
+            ...
+            ...");
+        assert_eq!(simulate(&grid, 3, &life("life", Edges::Clipped, vec![Stuck::At(1, 1)])), Ok(1));
+        assert_eq!(simulate(&grid, 0, &life("life", Edges::Clipped, vec![Stuck::Corners])), Ok(4));
+        assert_eq!(simulate(&grid, 1, &life("life", Edges::Clipped, vec![Stuck::At(3, 0)])),
+                   Err(StuckError { x: 3, y: 0, width: 3, height: 3 }));
+        assert_eq!("2,1".parse(), Ok(Stuck::At(2, 1)));
+        assert_eq!("corners".parse(), Ok(Stuck::Corners));
+        assert!("2;1".parse::<Stuck>().is_err());
     }
 }
\ No newline at end of file
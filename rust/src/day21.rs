@@ -2,6 +2,9 @@ extern crate utils;
 
 use std::env;
 use std::cmp;
+use std::error;
+use std::fmt;
+use std::str::FromStr;
 use std::io::{self, BufReader};
 use std::io::prelude::*;
 use std::fs::File;
@@ -23,40 +26,255 @@ impl Participant {
     }
 }
 
-#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
 struct Item {
-    name: &'static str,
+    name: String,
     cost: i32,
     damage: i32,
     armor: i32
 }
 
-const WEAPONS: [Item; 5] = [
-    Item { name: "Dagger",     cost: 8,  damage: 4, armor: 0 },
-    Item { name: "Shortsword", cost: 10, damage: 5, armor: 0 },
-    Item { name: "Warhammer",  cost: 25, damage: 6, armor: 0 },
-    Item { name: "Longsword",  cost: 40, damage: 7, armor: 0 },
-    Item { name: "Greataxe",   cost: 74, damage: 8, armor: 0 }
-];
-
-const ARMORS: [Item; 5] = [
-    Item { name: "Leather",    cost: 13,  damage: 0, armor: 1 },
-    Item { name: "Chainmail",  cost: 31,  damage: 0, armor: 2 },
-    Item { name: "Splintmail", cost: 53,  damage: 0, armor: 3 },
-    Item { name: "Bandedmail", cost: 75,  damage: 0, armor: 4 },
-    Item { name: "Platemail",  cost: 102, damage: 0, armor: 5 }
-];
-
-const RINGS: [Item; 6] = [
-    Item { name: "Damage +1",  cost: 25,  damage: 1, armor: 0 },
-    Item { name: "Damage +2",  cost: 50,  damage: 2, armor: 0 },
-    Item { name: "Damage +3",  cost: 100, damage: 3, armor: 0 },
-    Item { name: "Defense +1", cost: 20,  damage: 0, armor: 1 },
-    Item { name: "Defense +2", cost: 40,  damage: 0, armor: 2 },
-    Item { name: "Defense +3", cost: 80,  damage: 0, armor: 3 }
-];
-
-fn battle(boss: &Participant, player: &Participant) -> i32 {
+// A group of items of which between min and max different ones have to be bought
+#[derive(PartialEq, Debug)]
+struct Category {
+    name: String,
+    min: usize,
+    max: usize,
+    items: Vec<Item>
+}
+
+// The shop of the puzzle in the same format as a shop file given with --shop. The number of
+// items to buy from a category goes within parentheses in its header, either an exact number or
+// a range, and defaults to 0-1 when left out.
+const DEFAULT_SHOP: &str =
+"Weapons (1):     Cost  Damage  Armor
+Dagger        8     4       0
+Shortsword   10     5       0
+Warhammer    25     6       0
+Longsword    40     7       0
+Greataxe     74     8       0
+
+Armor (0-1):      Cost  Damage  Armor
+Leather      13     0       1
+Chainmail    31     0       2
+Splintmail   53     0       3
+Bandedmail   75     0       4
+Platemail   102     0       5
+
+Rings (0-2):      Cost  Damage  Armor
+Damage +1    25     1       0
+Damage +2    50     2       0
+Damage +3   100     3       0
+Defense +1   20     0       1
+Defense +2   40     0       2
+Defense +3   80     0       3
+";
+
+#[derive(PartialEq, Debug)]
+struct ShopError {
+    line: usize,
+    message: String
+}
+
+impl fmt::Display for ShopError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            0 => write!(f, "{}", self.message),
+            line => write!(f, "line {}: {}", line, self.message)
+        }
+    }
+}
+
+impl error::Error for ShopError {}
+
+#[derive(PartialEq, Debug)]
+struct Shop {
+    categories: Vec<Category>
+}
+
+impl Shop {
+    fn read<R: Read>(reader: BufReader<R>) -> Result<Shop, ShopError> {
+        let error = |line: usize, message: String| ShopError { line, message };
+        let mut categories: Vec<(usize, Category)> = vec![];
+
+        for (nr, line) in reader.lines().enumerate() {
+            let nr = nr + 1;
+            let line = line.map_err(|e| error(nr, e.to_string()))?;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some((header, _)) = line.split_once(':') {
+                let (name, min, max) = parse_header(header.trim()).ok_or_else(|| error(nr, format!("invalid category header '{}'", header)))?;
+                if categories.iter().any(|(_, c)| c.name == name) {
+                    return Err(error(nr, format!("there is already a category named {}", name)));
+                }
+                if min > max {
+                    return Err(error(nr, format!("{} needs at least {} but at most {} items", name, min, max)));
+                }
+                categories.push((nr, Category { name, min, max, items: vec![] }));
+                continue;
+            }
+
+            let (_, category) = categories.last_mut().ok_or_else(|| error(nr, "item before the first category".to_string()))?;
+            let words: Vec<_> = line.split_whitespace().collect();
+            if words.len() < 4 {
+                return Err(error(nr, format!("expected name, cost, damage and armor, got '{}'", line)));
+            }
+            let (name, numbers) = words.split_at(words.len() - 3);
+            let numbers = numbers.iter().map(|n| n.parse::<i32>()).collect::<Result<Vec<_>, _>>()
+                .map_err(|_| error(nr, format!("cost, damage and armor must be numbers, got '{}'", line)))?;
+            let name = name.join(" ");
+            if category.items.iter().any(|item| item.name == name) {
+                return Err(error(nr, format!("{} is already sold as {}", category.name, name)));
+            }
+            category.items.push(Item { name, cost: numbers[0], damage: numbers[1], armor: numbers[2] });
+        }
+
+        for (nr, category) in categories.iter() {
+            if category.items.len() < category.min {
+                return Err(error(*nr, format!("{} needs at least {} items but only has {}", category.name, category.min, category.items.len())));
+            }
+        }
+        if categories.is_empty() {
+            return Err(error(0, "no categories given".to_string()));
+        }
+
+        Ok(Shop { categories: categories.into_iter().map(|(_, c)| c).collect() })
+    }
+
+    // Every way of buying the allowed number of different items from each of the categories
+    fn loadouts(&self) -> Vec<Loadout<'_>> {
+        let mut loadouts = vec![Loadout { items: vec![], cost: 0, damage: 0, armor: 0 }];
+        for category in self.categories.iter() {
+            let mut choices = vec![];
+            for n in category.min..=cmp::min(category.max, category.items.len()) {
+                choose(&category.items, n, &mut vec![], &mut choices);
+            }
+            loadouts = loadouts.iter()
+                .flat_map(|loadout| choices.iter().map(move |choice| loadout.with(choice)))
+                .collect();
+        }
+        loadouts
+    }
+}
+
+// Parses "Name", "Name (n)" or "Name (min-max)"
+fn parse_header(header: &str) -> Option<(String, usize, usize)> {
+    match header.strip_suffix(')').and_then(|h| h.split_once('(')) {
+        Some((name, slots)) => {
+            let (min, max) = match slots.split_once('-') {
+                Some((min, max)) => (min.trim().parse().ok()?, max.trim().parse().ok()?),
+                None => {
+                    let n = slots.trim().parse().ok()?;
+                    (n, n)
+                }
+            };
+            Some((name.trim().to_string(), min, max)).filter(|(name, _, _)| !name.is_empty())
+        },
+        None if !header.contains('(') && !header.is_empty() => Some((header.to_string(), 0, 1)),
+        None => None
+    }
+}
+
+// All combinations of n items in the order they are listed in
+fn choose<'a>(items: &'a [Item], n: usize, chosen: &mut Vec<&'a Item>, choices: &mut Vec<Vec<&'a Item>>) {
+    if n == 0 {
+        choices.push(chosen.clone());
+        return;
+    }
+    for i in 0..items.len() {
+        if items.len() - i < n {
+            break;
+        }
+        chosen.push(&items[i]);
+        choose(&items[i + 1..], n - 1, chosen, choices);
+        chosen.pop();
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Loadout<'a> {
+    items: Vec<&'a Item>,
+    cost: i32,
+    damage: i32,
+    armor: i32
+}
+
+impl<'a> Loadout<'a> {
+    fn with(&self, items: &[&'a Item]) -> Loadout<'a> {
+        let mut loadout = self.clone();
+        for item in items.iter() {
+            loadout.items.push(item);
+            loadout.cost += item.cost;
+            loadout.damage += item.damage;
+            loadout.armor += item.armor;
+        }
+        loadout
+    }
+
+    fn player(&self, hp: i32) -> Participant {
+        Participant { hp, damage: self.damage, armor: self.armor }
+    }
+
+    fn names(&self) -> String {
+        self.items.iter().map(|item| item.name.as_str()).collect::<Vec<_>>().join(", ")
+    }
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+enum Winner {
+    Player, Boss
+}
+
+// Rounds counts single attacks, the player always attacks first
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+struct Outcome {
+    winner: Winner,
+    rounds: i32,
+    player_hp: i32,
+    boss_hp: i32
+}
+
+impl Outcome {
+    // How far ahead the player is when the battle ends, negative when the boss wins
+    fn margin(&self) -> i32 {
+        self.player_hp - self.boss_hp
+    }
+}
+
+// Each side needs ceil(hp / damage per attack) attacks to win and the player strikes first, so
+// wins on a tie. A side without any hit points has lost before the first attack.
+fn battle_outcome(boss: &Participant, player: &Participant) -> Outcome {
+    if player.hp <= 0 || boss.hp <= 0 {
+        let winner = if player.hp > 0 { Winner::Player } else { Winner::Boss };
+        return Outcome { winner, rounds: 0, player_hp: player.hp, boss_hp: boss.hp };
+    }
+
+    let player_damage = cmp::max(player.damage - boss.armor, 1);
+    let boss_damage = cmp::max(boss.damage - player.armor, 1);
+    let player_attacks = (boss.hp + player_damage - 1) / player_damage;
+    let boss_attacks = (player.hp + boss_damage - 1) / boss_damage;
+
+    if player_attacks <= boss_attacks {
+        Outcome {
+            winner: Winner::Player,
+            rounds: 2 * player_attacks - 1,
+            player_hp: player.hp - (player_attacks - 1) * boss_damage,
+            boss_hp: boss.hp - player_attacks * player_damage
+        }
+    } else {
+        Outcome {
+            winner: Winner::Boss,
+            rounds: 2 * boss_attacks,
+            player_hp: player.hp - boss_attacks * boss_damage,
+            boss_hp: boss.hp - boss_attacks * player_damage
+        }
+    }
+}
+
+fn battle(boss: &Participant, player: &Participant) -> Outcome {
     let players = [player, boss];
     let mut hps = [player.hp, boss.hp];
     let mut round = 0;
@@ -74,69 +292,171 @@ fn battle(boss: &Participant, player: &Participant) -> i32 {
         round += 1;
     }
 
-    hps[0] - hps[1]
-}
-
-fn solve(input: &Input) -> (i32, i32) {
-
-    let mut min_cost = std::i32::MAX;
-    let mut max_cost = 0;
-
-    for w_idx in 0..WEAPONS.len() {
-        for a_idx in -1..(ARMORS.len() as i32) {
-            for r0_idx in -1..(RINGS.len() as i32) {
-                for r1_idx in -1..(RINGS.len() as i32) {
-                    if r0_idx < 0 && r1_idx >= 0 || (r0_idx >= 0 && r0_idx == r1_idx) {
-                        continue;
-                    }
-
-                    let damage = WEAPONS[w_idx].damage
-                        + if a_idx  >= 0 { ARMORS[a_idx as usize].damage } else { 0 }
-                        + if r0_idx >= 0 { RINGS[r0_idx as usize].damage } else { 0 }
-                        + if r1_idx >= 0 { RINGS[r1_idx as usize].damage } else { 0 };
-                    let armor = WEAPONS[w_idx].armor
-                        + if a_idx  >= 0 { ARMORS[a_idx as usize].armor } else { 0 }
-                        + if r0_idx >= 0 { RINGS[r0_idx as usize].armor } else { 0 }
-                        + if r1_idx >= 0 { RINGS[r1_idx as usize].armor } else { 0 };
-                    let cost = WEAPONS[w_idx].cost
-                        + if a_idx  >= 0 { ARMORS[a_idx as usize].cost } else { 0 }
-                        + if r0_idx >= 0 { RINGS[r0_idx as usize].cost } else { 0 }
-                        + if r1_idx >= 0 { RINGS[r1_idx as usize].cost } else { 0 };
-
-                    let player = Participant {
-                        hp: 100,
-                        damage: damage,
-                        armor: armor
-                    };
-
-                    if battle(input, &player) > 0 {
-                        min_cost = cmp::min(min_cost, cost);
-                    } else {
-                        max_cost = cmp::max(max_cost, cost);
-                    }
-                }
+    Outcome { winner: if hps[0] > 0 { Winner::Player } else { Winner::Boss }, rounds: round as i32, player_hp: hps[0], boss_hp: hps[1] }
+}
+
+// The cheapest loadout the player wins with and the most expensive one the player loses with,
+// the battles are fought round by round when simulate is set
+fn solve<'a>(input: &Input, shop: &'a Shop, hp: i32, simulate: bool) -> (Option<Loadout<'a>>, Option<Loadout<'a>>) {
+    let mut cheapest_win: Option<Loadout> = None;
+    let mut dearest_loss: Option<Loadout> = None;
+
+    for loadout in shop.loadouts() {
+        let outcome = if simulate { battle(input, &loadout.player(hp)) } else { battle_outcome(input, &loadout.player(hp)) };
+        if outcome.winner == Winner::Player {
+            if cheapest_win.as_ref().is_none_or(|l| loadout.cost < l.cost) {
+                cheapest_win = Some(loadout);
             }
+        } else if dearest_loss.as_ref().is_none_or(|l| loadout.cost > l.cost) {
+            dearest_loss = Some(loadout);
         }
     }
 
-    (min_cost, max_cost)
+    (cheapest_win, dearest_loss)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Column {
+    Loadout, Cost, Damage, Armor, Result, Rounds, Margin
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Column, String> {
+        match s {
+            "loadout" => Ok(Column::Loadout),
+            "cost" => Ok(Column::Cost),
+            "damage" => Ok(Column::Damage),
+            "armor" => Ok(Column::Armor),
+            "result" => Ok(Column::Result),
+            "rounds" => Ok(Column::Rounds),
+            "margin" => Ok(Column::Margin),
+            _ => Err(format!("unknown column {}", s))
+        }
+    }
+}
+
+// Every loadout with how the battle goes, sorted by the given column with ties in cost order.
+// Loadouts on the Pareto frontier of cost against margin, those that no loadout beats on margin
+// without costing more, are marked with a *.
+fn report(input: &Input, shop: &Shop, hp: i32, sort: Column, descending: bool) -> String {
+    let mut rows: Vec<_> = shop.loadouts().into_iter().map(|loadout| {
+        let outcome = battle_outcome(input, &loadout.player(hp));
+        (loadout, outcome, false)
+    }).collect();
+
+    rows.sort_by(|(a, a_outcome, _), (b, b_outcome, _)| a.cost.cmp(&b.cost).then(b_outcome.margin().cmp(&a_outcome.margin())));
+    let mut best_margin = None;
+    for (_, outcome, frontier) in rows.iter_mut() {
+        if best_margin.is_none_or(|m| outcome.margin() > m) {
+            best_margin = Some(outcome.margin());
+            *frontier = true;
+        }
+    }
+
+    rows.sort_by(|(a, a_outcome, _), (b, b_outcome, _)| {
+        let ordering = match sort {
+            Column::Loadout => a.names().cmp(&b.names()),
+            Column::Cost => a.cost.cmp(&b.cost),
+            Column::Damage => a.damage.cmp(&b.damage),
+            Column::Armor => a.armor.cmp(&b.armor),
+            Column::Result => (a_outcome.winner == Winner::Boss).cmp(&(b_outcome.winner == Winner::Boss)),
+            Column::Rounds => a_outcome.rounds.cmp(&b_outcome.rounds),
+            Column::Margin => a_outcome.margin().cmp(&b_outcome.margin())
+        };
+        if descending { ordering.reverse() } else { ordering }
+    });
+
+    let width = rows.iter().map(|(loadout, _, _)| loadout.names().len()).max().unwrap_or(0).max("Loadout".len());
+    let mut table = format!("{:<w$}  {:>5}  {:>6}  {:>5}  {:<6}  {:>6}  {:>6}\n", "Loadout", "Cost", "Damage", "Armor", "Result", "Rounds", "Margin", w = width);
+    for (loadout, outcome, frontier) in rows.iter() {
+        let result = match outcome.winner { Winner::Player => "win", Winner::Boss => "lose" };
+        table.push_str(&format!("{:<w$}  {:>5}  {:>6}  {:>5}  {:<6}  {:>6}  {:>6}{}\n",
+            loadout.names(), loadout.cost, loadout.damage, loadout.armor, result, outcome.rounds, outcome.margin(),
+            if *frontier { " *" } else { "" }, w = width));
+    }
+    table
+}
+
+fn print_result(part: &str, loadout: Option<Loadout>, none: &str) {
+    match loadout {
+        Some(loadout) => println!("{}: {} ({})", part, loadout.cost, loadout.names()),
+        None => println!("{}: {}", part, none)
+    }
+}
+
+struct Options {
+    input: String,
+    shop: Option<String>,
+    hp: i32,
+    report: bool,
+    sort: Column,
+    descending: bool,
+    simulate: bool
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Options {
+        let mut input = None;
+        let mut shop = None;
+        let mut hp = 100;
+        let mut report = false;
+        let mut sort = Column::Cost;
+        let mut descending = false;
+        let mut simulate = false;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--shop" => shop = Some(args.next().expect("No shop file given")),
+                "--hp" => hp = args.next().and_then(|hp| hp.parse().ok()).expect("No valid player hp given"),
+                "--report" => report = true,
+                "--sort" => sort = args.next().expect("No sort column given").parse().unwrap(),
+                "--desc" => descending = true,
+                "--simulate" => simulate = true,
+                _ => input = Some(arg)
+            }
+        }
+
+        Options { input: input.expect("No input file given"), shop, hp, report, sort, descending, simulate }
+    }
 }
 
 fn main() {
+    let options = Options::parse(env::args().skip(1));
+
     measure(|| {
-        let input = input().expect("Input failed");
-        let (part1, part2) = solve(&input);
-        println!("Part1: {}", part1);
-        println!("Part2: {}", part2);
+        let input = input(&options.input).expect("Input failed");
+        let shop = match options.shop.as_ref() {
+            Some(path) => shop(path).expect("Shop file failed"),
+            None => default_shop()
+        };
+        if options.report {
+            print!("{}", report(&input, &shop, options.hp, options.sort, options.descending));
+            return;
+        }
+        let (part1, part2) = solve(&input, &shop, options.hp, options.simulate);
+        print_result("Part1", part1, "no loadout wins");
+        print_result("Part2", part2, "no loadout loses");
     });
 }
 
-fn input() -> io::Result<Input> {
-    let f = File::open(env::args().skip(1).next().expect("No input file given"))?;
+fn default_shop() -> Shop {
+    Shop::read(BufReader::new(DEFAULT_SHOP.as_bytes())).expect("Default shop is valid")
+}
+
+fn input(path: &str) -> io::Result<Input> {
+    let f = File::open(path)?;
     let f = BufReader::new(f);
     Ok(Input::read(f))
 }
 
+fn shop(path: &str) -> io::Result<Shop> {
+    let f = File::open(path)?;
+    let f = BufReader::new(f);
+    Shop::read(f).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -154,18 +474,105 @@ mod tests {
     #[test]
     fn test_battle() {
         let player = Participant { hp: 8, damage: 5, armor: 5 };
-        assert_eq!(battle(&as_input(INPUT), &player), 2);
+        assert_eq!(battle(&as_input(INPUT), &player).margin(), 2);
+    }
+
+    #[test]
+    fn test_battle_outcome() {
+        let boss = as_input(INPUT);
+        let player = Participant { hp: 8, damage: 5, armor: 5 };
+        assert_eq!(battle_outcome(&boss, &player), Outcome { winner: Winner::Player, rounds: 7, player_hp: 2, boss_hp: 0 });
+        assert_eq!(battle_outcome(&boss, &Participant { hp: 0, ..player }), Outcome { winner: Winner::Boss, rounds: 0, player_hp: 0, boss_hp: 12 });
+
+        for boss_hp in -2..30 {
+            for player_hp in -2..30 {
+                for (damage, armor) in [(0, 0), (3, 1), (5, 5), (9, 0), (2, 8)] {
+                    let boss = Participant { hp: boss_hp, damage: 7, armor: 2 };
+                    let player = Participant { hp: player_hp, damage, armor };
+                    assert_eq!(battle_outcome(&boss, &player), battle(&boss, &player), "{:?} against {:?}", player, boss);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_report() {
+        let shop = as_shop(
+           "Weapons (1):
+            Stick 1 1 0
+            Sword 10 4 0
+            Armor (0-1):
+            Cloak 5 0 3").unwrap();
+        let boss = Participant { hp: 8, damage: 4, armor: 0 };
+        assert_eq!(report(&boss, &shop, 8, Column::Cost, false),
+"Loadout        Cost  Damage  Armor  Result  Rounds  Margin
+Stick             1       1      0  lose         4      -6 *
+Stick, Cloak      6       1      3  win         15       1 *
+Sword            10       4      0  win          3       4 *
+Sword, Cloak     15       4      3  win          3       7 *
+");
+        let by_margin: Vec<_> = report(&boss, &shop, 8, Column::Margin, true).lines().skip(1).map(|l| l[..12].trim().to_string()).collect();
+        assert_eq!(by_margin, vec!["Sword, Cloak", "Sword", "Stick, Cloak", "Stick"]);
+        assert_eq!("rounds".parse::<Column>(), Ok(Column::Rounds));
+        assert!("speed".parse::<Column>().is_err());
     }
 
     #[test]
     fn test_part1() {
-        let (part1, _) = solve(&as_input(INPUT));
-        assert_eq!(part1, 8);
+        let shop = default_shop();
+        let (part1, _) = solve(&as_input(INPUT), &shop, 100, false);
+        assert_eq!(part1.map(|l| (l.cost, l.names())), Some((8, "Dagger".to_string())));
     }
 
     #[test]
     fn test_part2() {
-        let (_, part2) = solve(&as_input(INPUT));
-        assert_eq!(part2, 0);
+        let shop = default_shop();
+        let (_, part2) = solve(&as_input(INPUT), &shop, 100, false);
+        assert!(part2.is_none());
+
+        let boss = Participant { hp: 104, damage: 8, armor: 1 };
+        let (part1, part2) = solve(&boss, &shop, 100, false);
+        assert_eq!(solve(&boss, &shop, 100, true).1.map(|l| l.cost), part2.as_ref().map(|l| l.cost));
+        assert_eq!(part1.map(|l| (l.cost, l.names())), Some((78, "Longsword, Leather, Damage +1".to_string())));
+        assert_eq!(part2.map(|l| l.cost), Some(148));
+    }
+
+    fn as_shop(s: &str) -> Result<Shop, ShopError> {
+        Shop::read(BufReader::new(s.as_bytes()))
+    }
+
+    #[test]
+    fn test_shop() {
+        let shop = default_shop();
+        assert_eq!(shop.categories.iter().map(|c| (c.name.as_str(), c.min, c.max, c.items.len())).collect::<Vec<_>>(),
+                   vec![("Weapons", 1, 1, 5), ("Armor", 0, 1, 5), ("Rings", 0, 2, 6)]);
+        assert_eq!(shop.categories[2].items[3], Item { name: "Defense +1".to_string(), cost: 20, damage: 0, armor: 1 });
+        assert_eq!(shop.loadouts().len(), 5 * 6 * (1 + 6 + 15));
+
+        let shop = as_shop(
+           "Wands (2-3):
+            Oak 5 1 0
+            Elder wand 50 4 1
+            Yew 7 2 0
+            Trinkets:
+            Locket 3 0 1").unwrap();
+        let loadouts: Vec<_> = shop.loadouts().iter().map(|l| (l.names(), l.cost, l.damage, l.armor)).collect();
+        assert_eq!(loadouts, vec![
+            ("Oak, Elder wand".to_string(), 55, 5, 1), ("Oak, Elder wand, Locket".to_string(), 58, 5, 2),
+            ("Oak, Yew".to_string(), 12, 3, 0), ("Oak, Yew, Locket".to_string(), 15, 3, 1),
+            ("Elder wand, Yew".to_string(), 57, 6, 1), ("Elder wand, Yew, Locket".to_string(), 60, 6, 2),
+            ("Oak, Elder wand, Yew".to_string(), 62, 7, 1), ("Oak, Elder wand, Yew, Locket".to_string(), 65, 7, 2)
+        ]);
+
+        let error = |s: &str| as_shop(s).unwrap_err();
+        assert_eq!(error("Dagger 8 4 0"), ShopError { line: 1, message: "item before the first category".to_string() });
+        assert_eq!(error("Weapons (1):\nDagger 8 four 0").message, "cost, damage and armor must be numbers, got 'Dagger 8 four 0'");
+        assert_eq!(error("Weapons (1):\nDagger 8 4").message, "expected name, cost, damage and armor, got 'Dagger 8 4'");
+        assert_eq!(error("Weapons (1):\nDagger 8 4 0\nDagger 9 4 0").message, "Weapons is already sold as Dagger");
+        assert_eq!(error("Weapons (2):\nDagger 8 4 0").message, "Weapons needs at least 2 items but only has 1");
+        assert_eq!(error("Weapons (2-1):").message, "Weapons needs at least 2 but at most 1 items");
+        assert_eq!(error("Weapons (x):").message, "invalid category header 'Weapons (x)'");
+        assert_eq!(error("Rings:\nRings:").line, 2);
+        assert_eq!(error("").message, "no categories given");
     }
 }
\ No newline at end of file
@@ -1,9 +1,12 @@
-#[macro_use] extern crate lazy_static;
 extern crate utils;
 
 use std::env;
-use std::cmp;
-use std::collections::VecDeque;
+use std::error;
+use std::fmt;
+use std::cmp::{self, Ordering};
+use std::collections::{BinaryHeap, HashSet, VecDeque};
+use std::mem;
+use std::str::FromStr;
 use std::io::{self, BufReader};
 use std::io::prelude::*;
 use std::fs::File;
@@ -33,12 +36,26 @@ struct Player {
     spent: i32
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum EffectModifier {
     Damage, Healing, Armor, Mana
 }
 
-#[derive(Debug)]
+impl FromStr for EffectModifier {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<EffectModifier, ()> {
+        match s {
+            "damage" => Ok(Damage),
+            "healing" => Ok(Healing),
+            "armor" => Ok(Armor),
+            "mana" => Ok(Mana),
+            _ => Err(())
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
 struct Effect {
     modifier: EffectModifier,
     value: i32,
@@ -62,139 +79,469 @@ impl Effect {
             }
         }
     }
+
+    fn describe_immediate(&self) -> String {
+        match self.modifier {
+            Damage => format!("dealing {} damage", self.value),
+            Healing => format!("healing {} hit points", self.value),
+            Armor => format!("increasing armor by {}", self.value),
+            Mana => format!("providing {} mana", self.value)
+        }
+    }
+
+    fn describe_tick(&self, name: &str, timer: i32) -> String {
+        match self.modifier {
+            Damage => format!("{} deals {} damage; its timer is now {}.", name, self.value, timer),
+            Healing => format!("{} heals {} hit points; its timer is now {}.", name, self.value, timer),
+            Armor => format!("{}'s timer is now {}.", name, timer),
+            Mana => format!("{} provides {} mana; its timer is now {}.", name, self.value, timer)
+        }
+    }
+
+    fn describe_wear_off(&self, name: &str) -> String {
+        match self.modifier {
+            Armor => format!("{} wears off, decreasing armor by {}.", name, self.value),
+            _ => format!("{} wears off.", name)
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 struct Spell {
-    name: &'static str,
+    name: String,
     cost: i32,
     effects: Vec<Effect>
 }
 
-lazy_static! {
-    static ref SPELLS: [Spell; 5] = [
-        Spell {
-            name: "Magic Missile",
-            cost: 53,
-            effects: vec![Effect { modifier: Damage, value: 4, turns: 0 }]
-        },
-        Spell {
-            name: "Drain",
-            cost: 73,
-            effects: vec![Effect { modifier: Damage, value: 2, turns: 0 }, Effect { modifier: Healing, value: 2, turns: 0 }]
-        },
-        Spell {
-            name: "Shield",
-            cost: 113,
-            effects: vec![Effect { modifier: Armor, value: 7, turns: 6 }]
-        },
-        Spell {
-            name: "Poison",
-            cost: 173,
-            effects: vec![Effect { modifier: Damage, value: 3, turns: 6 }]
-        },
-        Spell {
-            name: "Recharge",
-            cost: 229,
-            effects: vec![Effect { modifier: Mana, value: 101, turns: 5 }]
-        },
-    ];
+// The spells of the puzzle in the same format as a spell file given with --spells
+const DEFAULT_SPELLS: &str =
+"[[spell]]
+name = \"Magic Missile\"
+cost = 53
+damage = 4
+
+[[spell]]
+name = \"Drain\"
+cost = 73
+damage = 2
+healing = 2
+
+[[spell]]
+name = \"Shield\"
+cost = 113
+armor = 7
+turns = 6
+
+[[spell]]
+name = \"Poison\"
+cost = 173
+damage = 3
+turns = 6
+
+[[spell]]
+name = \"Recharge\"
+cost = 229
+mana = 101
+turns = 5
+";
+
+fn default_spells() -> Vec<Spell> {
+    Spellbook::read(BufReader::new(DEFAULT_SPELLS.as_bytes())).expect("Default spells are valid").spells
+}
+
+// Line 0 is used for problems with the file as a whole
+#[derive(Debug, PartialEq)]
+struct SpellsError {
+    line: usize,
+    message: String
+}
+
+impl fmt::Display for SpellsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "line {}: {}", self.line, self.message)
+        }
+    }
+}
+
+impl error::Error for SpellsError {}
+
+// A spell file is a small subset of TOML. Every spell is a [[spell]] table with a name, a cost,
+// one or more of damage, healing, armor and mana and optionally the number of turns its effects
+// last, zero or no turns means the effects are applied right away. An optional [boss] table with
+// hp and damage replaces the boss from the puzzle input.
+#[derive(Debug)]
+struct Spellbook {
+    spells: Vec<Spell>,
+    boss: Option<Boss>
+}
+
+impl Spellbook {
+    fn read<R: Read>(reader: BufReader<R>) -> Result<Spellbook, SpellsError> {
+        enum Section { Top, Spell, Boss }
+
+        struct Entry {
+            line: usize,
+            name: Option<String>,
+            cost: Option<i32>,
+            turns: i32,
+            effects: Vec<(EffectModifier, i32)>
+        }
+
+        let error = |line: usize, message: String| SpellsError { line, message };
+        let mut section = Section::Top;
+        let mut entries: Vec<Entry> = vec![];
+        let (mut boss_line, mut boss_hp, mut boss_damage) = (0, None, None);
+
+        for (nr, line) in reader.lines().enumerate() {
+            let nr = nr + 1;
+            let line = line.map_err(|e| error(nr, e.to_string()))?;
+            let line = strip_comment(&line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            match line {
+                "[[spell]]" => {
+                    section = Section::Spell;
+                    entries.push(Entry { line: nr, name: None, cost: None, turns: 0, effects: vec![] });
+                    continue;
+                },
+                "[boss]" => {
+                    section = Section::Boss;
+                    boss_line = nr;
+                    continue;
+                },
+                _ if line.starts_with('[') => return Err(error(nr, format!("unknown section {}", line))),
+                _ => {}
+            }
+
+            let (key, value) = line.split_once('=').ok_or_else(|| error(nr, format!("expected key = value, got '{}'", line)))?;
+            let (key, value) = (key.trim(), value.trim());
+            let number = || value.parse::<i32>().map_err(|_| error(nr, format!("{} must be a number, got '{}'", key, value)));
+
+            match section {
+                Section::Top => return Err(error(nr, format!("{} is outside of a [[spell]] or [boss] table", key))),
+                Section::Boss => match key {
+                    "hp" if boss_hp.is_none() => boss_hp = Some(number()?),
+                    "damage" if boss_damage.is_none() => boss_damage = Some(number()?),
+                    "hp" | "damage" => return Err(error(nr, format!("boss {} is given twice", key))),
+                    _ => return Err(error(nr, format!("unknown boss key {}", key)))
+                },
+                Section::Spell => {
+                    let entry = entries.last_mut().unwrap();
+                    match key {
+                        "name" if entry.name.is_none() => {
+                            let name = value.strip_prefix('"').and_then(|v| v.strip_suffix('"'))
+                                .ok_or_else(|| error(nr, format!("name must be a quoted string, got '{}'", value)))?;
+                            entry.name = Some(name.to_string());
+                        },
+                        "cost" if entry.cost.is_none() => {
+                            let cost = number()?;
+                            // The search for the least mana spent relies on casting never refunding any
+                            if cost < 0 {
+                                return Err(error(nr, format!("cost can't be negative, got {}", cost)));
+                            }
+                            entry.cost = Some(cost);
+                        },
+                        "turns" => {
+                            entry.turns = number()?;
+                            if entry.turns < 0 {
+                                return Err(error(nr, format!("turns can't be negative, got {}", entry.turns)));
+                            }
+                        },
+                        "name" | "cost" => return Err(error(nr, format!("spell {} is given twice", key))),
+                        _ => match key.parse::<EffectModifier>() {
+                            Ok(modifier) if entry.effects.iter().any(|(m, _)| *m == modifier) =>
+                                return Err(error(nr, format!("spell {} is given twice", key))),
+                            Ok(modifier) => entry.effects.push((modifier, number()?)),
+                            Err(_) => return Err(error(nr, format!("unknown spell key {}", key)))
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut spells: Vec<Spell> = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let line = entry.line;
+            let name = entry.name.ok_or_else(|| error(line, "spell has no name".to_string()))?;
+            if spells.iter().any(|spell| spell.name == name) {
+                return Err(error(line, format!("there is already a spell named {}", name)));
+            }
+            let cost = entry.cost.ok_or_else(|| error(line, format!("{} has no cost", name)))?;
+            if entry.effects.is_empty() {
+                return Err(error(line, format!("{} has no effects", name)));
+            }
+            let turns = entry.turns;
+            let effects = entry.effects.iter().map(|&(modifier, value)| Effect { modifier, value, turns }).collect();
+            spells.push(Spell { name, cost, effects });
+        }
+        let boss = match (boss_hp, boss_damage) {
+            (Some(hp), Some(damage)) => Some(Boss { hp, damage }),
+            (None, None) => None,
+            _ => return Err(error(boss_line, "boss needs both hp and damage".to_string()))
+        };
+
+        if spells.is_empty() {
+            return Err(error(0, "no spells given".to_string()));
+        }
+
+        Ok(Spellbook { spells, boss })
+    }
+}
+
+// Removes a trailing # comment, a # inside a quoted name is kept
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Affordability {
+    CheaperThanMana, UpToMana
+}
+
+// The points where the puzzle description leaves room for interpretation
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Rules {
+    // Whether a spell has to cost less than the mana left or may use up all of it
+    affordability: Affordability,
+    // Whether the player loses as soon as the mana left reaches zero
+    out_of_mana_loses: bool,
+    // Hit points the player loses at the start of each of their turns, 1 in hard mode
+    drain: i32,
+    // The least damage a boss attack deals however much armor the player has
+    min_damage: Option<i32>
+}
+
+impl Rules {
+    // The rules the solutions have always been computed with
+    const ORIGINAL: Rules = Rules { affordability: Affordability::CheaperThanMana, out_of_mana_loses: true, drain: 0, min_damage: None };
+    // The rules as written in the puzzle description
+    const STRICT: Rules = Rules { affordability: Affordability::UpToMana, out_of_mana_loses: false, drain: 0, min_damage: Some(1) };
+
+    fn hard(&self) -> Rules {
+        Rules { drain: 1, ..*self }
+    }
+
+    fn affordable(&self, cost: i32, mana: i32) -> bool {
+        match self.affordability {
+            Affordability::CheaperThanMana => cost < mana,
+            Affordability::UpToMana => cost <= mana
+        }
+    }
 }
 
-type NextSpellsFn = Fn(&Step) -> Vec<usize>;
+impl FromStr for Rules {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Rules, String> {
+        match s {
+            "original" => Ok(Rules::ORIGINAL),
+            "strict" => Ok(Rules::STRICT),
+            _ => Err(format!("unknown rules {}, expected original or strict", s))
+        }
+    }
+}
+
+type NextSpellsFn = dyn for<'a> Fn(&Step<'a>) -> Vec<usize>;
 
 fn default_next_spells(step: &Step) -> Vec<usize> {
-    let mut next_spells = Vec::with_capacity(SPELLS.len());
-    for (idx, spell) in SPELLS.iter().enumerate() {
-        if spell.cost < step.player.mana && !step.effects.iter().any(|(_, spell_idx, _)| *spell_idx == idx) {
+    let mut next_spells = Vec::with_capacity(step.spellbook.len());
+    for (idx, spell) in step.spellbook.iter().enumerate() {
+        if step.rules.affordable(spell.cost, step.player.mana) && !step.effects.iter().any(|(_, spell_idx, _)| *spell_idx == idx) {
             next_spells.push(idx);
         }
     }
     next_spells
 }
 
+// The state at the start of a turn, after the hard mode drain but before any effects are applied
+#[derive(Clone, Debug, PartialEq)]
+struct TurnState {
+    turn: i32,
+    player_hp: i32,
+    player_mana: i32,
+    armor: i32,
+    boss_hp: i32,
+    effects: Vec<(usize, i32)>,
+    cast: Option<usize>
+}
+
 #[derive(Debug)]
-struct Step {
+struct Victory {
+    mana: i32,
+    spells: Vec<usize>,
+    turns: Vec<TurnState>,
+    // The state once the battle is over, after the last cast or effects
+    end: TurnState,
+    narrative: Vec<String>
+}
+
+#[derive(Clone, Debug)]
+struct Step<'a> {
+    spellbook: &'a [Spell],
+    rules: Rules,
     turn: i32,
     boss: Boss,
     player: Player,
-    effects: Vec<(i32, usize, usize)>
+    effects: Vec<(i32, usize, usize)>,
+    spells: Vec<usize>,
+    log: Option<Vec<String>>
 }
 
-impl Step {
+impl<'a> Step<'a> {
+    fn new(boss: &Boss, player: &Player, spellbook: &'a [Spell], rules: &Rules) -> Step<'a> {
+        Step { spellbook, rules: *rules, turn: 0, boss: boss.clone(), player: player.clone(), effects: vec![], spells: vec![], log: None }
+    }
+
+    // Nothing happens any more once a cast has ended the battle
     fn run_battle(&mut self) {
-        if self.player.hp > 0 {
-            #[cfg(feature = "print")] {
-                println!("-- {} turn --", if self.turn % 2 == 0 { "Player" } else { "Boss" });
-            }
+        if !self.finished() {
+            let turn = if self.player_turn() { "Player" } else { "Boss" };
+            let (hp, armor, mana, boss_hp) = (self.player.hp, self.armor(), self.player.mana, self.boss.hp);
+            self.narrate(|| format!("-- {} turn --", turn));
+            self.narrate(|| format!("- Player has {} hit points, {} armor, {} mana", hp, armor, mana));
+            self.narrate(|| format!("- Boss has {} hit points", boss_hp));
 
             let mut armor = 0;
+            let spellbook = self.spellbook;
 
-            #[cfg(feature = "print")] {
-                println!("- Player has {} hit points, {} armor, {} mana", self.player.hp, armor, self.player.mana);
-                println!("- Boss has {} hit points", self.boss.hp);
-            }
-
-            let mut effects = Vec::with_capacity(self.effects.len());
-            for (timer, spell_idx, effect_idx) in self.effects.iter() {
-                let spell = &SPELLS[*spell_idx];
-                let effect = &spell.effects[*effect_idx];
+            for (timer, spell_idx, effect_idx) in mem::take(&mut self.effects) {
+                let spell = &spellbook[spell_idx];
+                let effect = &spell.effects[effect_idx];
                 effect.apply(&mut self.boss, &mut self.player, &mut armor);
+                self.narrate(|| effect.describe_tick(&spell.name, timer - 1));
 
-                #[cfg(feature = "print")] {
-                    println!("{} applies {} {:?}; its timer is now {}.", spell.name, effect.value, effect.modifier, timer - 1);
-                }
-
-                if *timer > 1 {
-                    effects.push((timer - 1, *spell_idx, *effect_idx));
+                if timer > 1 {
+                    self.effects.push((timer - 1, spell_idx, effect_idx));
                 } else {
-                    #[cfg(feature = "print")] {
-                        println!("{} wears off.", spell.name);
-                    }
+                    self.narrate(|| effect.describe_wear_off(&spell.name));
                 }
             }
-            self.effects = effects;
 
             if self.boss_turn() && !self.finished() {
-                #[cfg(feature = "print")] {
-                    println!("Boss attacks for {} - {} = {} damage.", self.boss.damage, armor, self.boss.damage - armor);
-                }
-                self.player.hp -= self.boss.damage - armor;
+                let attack = self.boss.damage;
+                let damage = self.rules.min_damage.map_or(attack - armor, |min| cmp::max(min, attack - armor));
+                self.narrate(|| if armor == 0 {
+                    format!("Boss attacks for {} damage.", damage)
+                } else {
+                    format!("Boss attacks for {} - {} = {} damage!", attack, armor, damage)
+                });
+                self.player.hp -= damage;
             }
         }
     }
 
-    fn next_steps(&mut self, next_spells: &NextSpellsFn) -> Vec<Step> {
-        let mut next = Vec::with_capacity(SPELLS.len());
+    fn next_steps(&mut self, next_spells: &NextSpellsFn) -> Vec<Step<'a>> {
+        let mut next = Vec::with_capacity(self.spellbook.len());
         if self.player_turn() {
-            for spell_idx in next_spells(&self) {
-                let spell = &SPELLS[spell_idx];
-                let mut player = self.player.clone();
-                let mut boss = self.boss.clone();
-                #[cfg(feature = "print")] {
-                    println!("Player casts {}", spell.name);
-                }
-                player.mana -= spell.cost;
-                player.spent += spell.cost;
-                let mut next_effects = self.effects.clone();
-
-                for (i, effect) in spell.effects.iter().enumerate() {
-                    if effect.turns == 0 {
-                        #[cfg(feature = "print")] {
-                            println!("{} applies {} {:?}.", spell.name, effect.value, effect.modifier);
-                        }
-                        let mut armor = 0;
-                        effect.apply(&mut boss, &mut player, &mut armor);
-                    } else {
-                        next_effects.push((effect.turns, spell_idx, i));
-                    }
-                }
-                next.push(Step { turn: self.turn + 1, boss: boss, player: player, effects: next_effects });
+            for spell_idx in next_spells(self) {
+                next.push(self.cast(spell_idx));
             }
         }
         next
     }
 
+    fn cast(&self, spell_idx: usize) -> Step<'a> {
+        let spell = &self.spellbook[spell_idx];
+        let mut player = self.player.clone();
+        let mut boss = self.boss.clone();
+        player.mana -= spell.cost;
+        player.spent += spell.cost;
+        let mut next_effects = self.effects.clone();
+        let mut immediate = vec![];
+
+        for (i, effect) in spell.effects.iter().enumerate() {
+            if effect.turns == 0 {
+                let mut armor = 0;
+                effect.apply(&mut boss, &mut player, &mut armor);
+                immediate.push(effect.describe_immediate());
+            } else {
+                next_effects.push((effect.turns, spell_idx, i));
+            }
+        }
+
+        let mut spells = self.spells.clone();
+        spells.push(spell_idx);
+        let mut step = Step { spellbook: self.spellbook, rules: self.rules, turn: self.turn + 1, boss, player, effects: next_effects, spells, log: self.log.clone() };
+        step.narrate(|| if immediate.is_empty() {
+            format!("Player casts {}.", spell.name)
+        } else {
+            format!("Player casts {}, {}.", spell.name, immediate.join(", and "))
+        });
+        step
+    }
+
+    // Lines are only built when replaying or when the print feature is enabled
+    fn narrate<F: FnOnce() -> String>(&mut self, line: F) {
+        #[cfg(not(feature = "print"))] {
+            if let Some(log) = self.log.as_mut() {
+                log.push(line());
+            }
+        }
+        #[cfg(feature = "print")] {
+            let line = line();
+            println!("{}", line);
+            if let Some(log) = self.log.as_mut() {
+                log.push(line);
+            }
+        }
+    }
+
+    fn narrate_ending(&mut self) {
+        if self.player_wins() {
+            self.narrate(|| "This kills the boss, and the player wins.".to_string());
+        } else {
+            self.narrate(|| "The boss wins.".to_string());
+        }
+    }
+
+    fn armor(&self) -> i32 {
+        self.effects.iter()
+            .map(|(_, spell_idx, effect_idx)| &self.spellbook[*spell_idx].effects[*effect_idx])
+            .filter(|effect| matches!(effect.modifier, Armor))
+            .map(|effect| effect.value)
+            .sum()
+    }
+
+    // Everything the rest of the battle depends on, the mana spent so far and the turn number are
+    // left out so a cheaper way of reaching the same situation makes the dearer ones redundant.
+    // Whose turn it is stays in since the drain and the spells to choose from depend on it.
+    fn key(&self) -> BattleKey {
+        let mut effects: Vec<(usize, usize, i32)> = self.effects.iter().map(|&(timer, spell_idx, effect_idx)| (spell_idx, effect_idx, timer)).collect();
+        effects.sort_unstable();
+        BattleKey { player_turn: self.player_turn(), player_hp: self.player.hp, player_mana: self.player.mana, boss_hp: self.boss.hp, effects }
+    }
+
+    fn state(&self) -> TurnState {
+        let mut effects: Vec<(usize, i32)> = vec![];
+        for (timer, spell_idx, _) in self.effects.iter() {
+            if !effects.iter().any(|(idx, _)| idx == spell_idx) {
+                effects.push((*spell_idx, *timer));
+            }
+        }
+        TurnState {
+            turn: self.turn,
+            player_hp: self.player.hp,
+            player_mana: self.player.mana,
+            armor: self.armor(),
+            boss_hp: self.boss.hp,
+            effects,
+            cast: None
+        }
+    }
+
     fn player_turn(&self) -> bool {
         self.turn % 2 == 0
     }
@@ -203,39 +550,127 @@ impl Step {
         self.turn % 2 == 1
     }
 
-    fn player_lose_one(&mut self) {
+    fn drain(&mut self) {
         if self.player_turn() {
-            self.player.hp -= 1;
+            self.player.hp -= self.rules.drain;
         }
     }
 
+    fn out_of_mana(&self) -> bool {
+        self.rules.out_of_mana_loses && self.player.mana <= 0
+    }
+
     fn finished(&self) -> bool {
-        self.boss.hp <= 0 || self.player.hp <= 0 || self.player.mana <= 0
+        self.boss.hp <= 0 || self.player.hp <= 0 || self.out_of_mana()
     }
 
     fn player_wins(&self) -> bool {
-        self.boss.hp <= 0 && self.player.hp > 0 && self.player.mana > 0
+        self.boss.hp <= 0 && self.player.hp > 0 && !self.out_of_mana()
     }
 }
 
-fn least_mana(boss: &Boss, player: &Player, next_spells: &NextSpellsFn, player_lose_one: bool) -> i32 {
-    let mut min_mana = std::i32::MAX;
-    let mut queue = VecDeque::new();
-    queue.push_front(Step { turn: 0, boss: boss.clone(), player: player.clone(), effects: vec![] });
+#[derive(PartialEq, Eq, Hash)]
+struct BattleKey {
+    player_turn: bool,
+    player_hp: i32,
+    player_mana: i32,
+    boss_hp: i32,
+    effects: Vec<(usize, usize, i32)>
+}
 
-    while let Some(mut step) = queue.pop_front() {
-        if player_lose_one {
-            step.player_lose_one();
+struct QueueStep<'a> {
+    step: Step<'a>
+}
+
+impl<'a> Ord for QueueStep<'a> {
+    fn cmp(&self, other: &QueueStep<'a>) -> Ordering {
+        other.step.player.spent.cmp(&self.step.player.spent)
+    }
+}
+
+impl<'a> PartialOrd for QueueStep<'a> {
+    fn partial_cmp(&self, other: &QueueStep<'a>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'a> PartialEq for QueueStep<'a> {
+    fn eq(&self, other: &QueueStep<'a>) -> bool {
+        self.step.player.spent == other.step.player.spent
+    }
+}
+
+impl<'a> Eq for QueueStep<'a> {}
+
+fn least_mana(boss: &Boss, player: &Player, spellbook: &[Spell], next_spells: &NextSpellsFn, rules: &Rules) -> Option<Victory> {
+    cheapest_win(Step::new(boss, player, spellbook, rules), next_spells).map(|(mana, spells)| {
+        let (turns, end, narrative) = replay(boss, player, spellbook, &spells, rules);
+        Victory { mana, spells, turns, end, narrative }
+    })
+}
+
+// Dijkstra over the player's turns with the mana spent as cost, starting at the beginning of the
+// player turn in start. A player turn together with the boss turn following each cast is expanded
+// at once so the queue only holds states at the start of a player turn, each of which is expanded
+// once. Returns the total mana spent and all spells cast including the ones before start.
+fn cheapest_win(start: Step, next_spells: &NextSpellsFn) -> Option<(i32, Vec<usize>)> {
+    let mut best: Option<(i32, Vec<usize>)> = None;
+    let mut visited = HashSet::new();
+    let mut heap = BinaryHeap::new();
+    heap.push(QueueStep { step: Step { log: None, ..start } });
+
+    while let Some(QueueStep { mut step }) = heap.pop() {
+        if best.as_ref().is_some_and(|(mana, _)| step.player.spent >= *mana) {
+            break;
+        }
+        if !visited.insert(step.key()) {
+            continue;
         }
 
+        step.drain();
         step.run_battle();
         if step.finished() {
             if step.player_wins() {
-                min_mana = cmp::min(min_mana, step.player.spent);
+                best = Some((step.player.spent, step.spells));
+            }
+            continue;
+        }
+
+        for mut next_step in step.next_steps(next_spells) {
+            next_step.run_battle();
+            if next_step.finished() {
+                if next_step.player_wins() && best.as_ref().is_none_or(|(mana, _)| next_step.player.spent < *mana) {
+                    best = Some((next_step.player.spent, next_step.spells));
+                }
+            } else {
+                next_step.turn += 1;
+                heap.push(QueueStep { step: next_step });
             }
+        }
+    }
+
+    best
+}
+
+// The original depth first search only pruning on the mana spent, kept to compare against
+fn least_mana_dfs(boss: &Boss, player: &Player, spellbook: &[Spell], next_spells: &NextSpellsFn, rules: &Rules) -> Option<Victory> {
+    let mut min_mana = std::i32::MAX;
+    let mut best = None;
+    let mut queue = VecDeque::new();
+    queue.push_front(Step::new(boss, player, spellbook, rules));
+
+    while let Some(mut step) = queue.pop_front() {
+        step.drain();
+
+        step.run_battle();
+        if step.finished() {
             #[cfg(feature = "print")] {
                 println!("{} wins.", if step.player_wins() { "Player" } else { "Boss" });
             }
+            if step.player_wins() && step.player.spent < min_mana {
+                min_mana = step.player.spent;
+                best = Some(step.spells);
+            }
         } else {
             if step.boss_turn() {
                 step.turn += 1;
@@ -254,32 +689,285 @@ fn least_mana(boss: &Boss, player: &Player, next_spells: &NextSpellsFn, player_l
         }
     }
 
-    min_mana
+    best.map(|spells| {
+        let (turns, end, narrative) = replay(boss, player, spellbook, &spells, rules);
+        Victory { mana: min_mana, spells, turns, end, narrative }
+    })
+}
+
+// Plays the given casts with the same rules as the search, recording the state of every turn, the
+// state the battle ends in and the battle in the words of the puzzle description
+fn replay(boss: &Boss, player: &Player, spellbook: &[Spell], spells: &[usize], rules: &Rules) -> (Vec<TurnState>, TurnState, Vec<String>) {
+    let mut step = Step::new(boss, player, spellbook, rules);
+    step.log = Some(vec![]);
+    let mut turns = vec![];
+
+    loop {
+        step.drain();
+        turns.push(step.state());
+
+        step.run_battle();
+        if step.finished() {
+            break;
+        }
+        if step.boss_turn() {
+            step.turn += 1;
+        } else {
+            match spells.get(step.spells.len()) {
+                Some(&spell_idx) => {
+                    step = step.cast(spell_idx);
+                    turns.last_mut().unwrap().cast = Some(spell_idx);
+                    if step.finished() {
+                        break;
+                    }
+                },
+                None => break
+            }
+        }
+        step.narrate(String::new);
+    }
+
+    step.narrate_ending();
+    let end = step.state();
+    (turns, end, step.log.unwrap_or_default())
+}
+
+#[derive(Debug, PartialEq)]
+enum PlayOutcome {
+    Won { spent: i32 }, Lost, Quit
+}
+
+// The battle fought turn by turn with spells chosen from commands, either a spell number from
+// the list, a spell name, hint for the cheapest way to win from here or quit
+fn play<R: BufRead>(boss: &Boss, player: &Player, spellbook: &[Spell], rules: &Rules, commands: R) -> PlayOutcome {
+    let flush = |step: &mut Step| {
+        for line in step.log.iter_mut().flat_map(|log| log.drain(..)) {
+            println!("{}", line);
+        }
+    };
+
+    let mut step = Step::new(boss, player, spellbook, rules);
+    step.log = Some(vec![]);
+    let mut commands = commands.lines();
+
+    loop {
+        let turn_start = step.clone();
+        step.drain();
+        step.run_battle();
+        if step.finished() {
+            step.narrate_ending();
+            flush(&mut step);
+            break;
+        }
+        flush(&mut step);
+
+        let effects: Vec<String> = step.state().effects.iter().map(|(idx, timer)| format!("{} ({})", spellbook[*idx].name, timer)).collect();
+        println!("Player: {} hit points, {} mana, {} mana spent", step.player.hp, step.player.mana, step.player.spent);
+        println!("Boss: {} hit points", step.boss.hp);
+        println!("Effects: {}", if effects.is_empty() { "none".to_string() } else { effects.join(", ") });
+        let castable = default_next_spells(&step);
+        for (nr, idx) in castable.iter().enumerate() {
+            println!("  {}) {} ({} mana)", nr + 1, spellbook[*idx].name, spellbook[*idx].cost);
+        }
+        if castable.is_empty() {
+            println!("No spell can be cast, the boss wins.");
+            return PlayOutcome::Lost;
+        }
+
+        let spell_idx = loop {
+            let line = match commands.next() {
+                Some(line) => line.unwrap(),
+                None => return PlayOutcome::Quit
+            };
+            let command = line.trim();
+            match command {
+                "" => continue,
+                "q" | "quit" => return PlayOutcome::Quit,
+                "h" | "hint" => {
+                    match cheapest_win(turn_start.clone(), &default_next_spells) {
+                        Some((mana, spells)) => println!("Hint: cast {}, winning from here costs {} more mana",
+                            spellbook[spells[step.spells.len()]].name, mana - step.player.spent),
+                        None => println!("Hint: there is no way to win from here")
+                    }
+                    continue;
+                },
+                _ => {}
+            }
+
+            let chosen = match command.parse::<usize>() {
+                Ok(nr) => castable.get(nr.wrapping_sub(1)).cloned(),
+                Err(_) => spellbook.iter().position(|spell| spell.name.eq_ignore_ascii_case(command))
+            };
+            match chosen {
+                Some(idx) if castable.contains(&idx) => break idx,
+                Some(idx) => println!("{} can't be cast now", spellbook[idx].name),
+                None => println!("Unknown spell or command: {}", command)
+            }
+        };
+
+        println!();
+        step = step.cast(spell_idx);
+        flush(&mut step);
+        if step.finished() {
+            step.narrate_ending();
+            flush(&mut step);
+            break;
+        }
+        step.narrate(String::new);
+
+        step.run_battle();
+        if step.finished() {
+            step.narrate_ending();
+            flush(&mut step);
+            break;
+        }
+        step.turn += 1;
+        step.narrate(String::new);
+        flush(&mut step);
+    }
+
+    if step.player_wins() {
+        PlayOutcome::Won { spent: step.player.spent }
+    } else {
+        PlayOutcome::Lost
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Search {
+    Dijkstra, DepthFirst
+}
+
+fn search(boss: &Boss, player: &Player, spellbook: &[Spell], next_spells: &NextSpellsFn, rules: &Rules, search: Search) -> Option<Victory> {
+    match search {
+        Search::Dijkstra => least_mana(boss, player, spellbook, next_spells, rules),
+        Search::DepthFirst => least_mana_dfs(boss, player, spellbook, next_spells, rules)
+    }
+}
+
+fn part1(boss: &Boss, player: &Player, spellbook: &[Spell], next_spells: &NextSpellsFn, rules: &Rules, search: Search) -> Option<Victory> {
+    self::search(boss, player, spellbook, next_spells, rules, search)
+}
+
+fn part2(boss: &Boss, player: &Player, spellbook: &[Spell], next_spells: &NextSpellsFn, rules: &Rules, search: Search) -> Option<Victory> {
+    self::search(boss, player, spellbook, next_spells, &rules.hard(), search)
+}
+
+fn format_result(part: &str, victory: Option<Victory>, spellbook: &[Spell], replay: bool) -> String {
+    match victory {
+        Some(victory) => {
+            let names: Vec<&str> = victory.spells.iter().map(|idx| spellbook[*idx].name.as_str()).collect();
+            let mut lines = vec![
+                format!("{}: {} ({})", part, victory.mana, names.join(", ")),
+                format!("  won after {} turns with {} hit points and {} mana left", victory.turns.len(), victory.end.player_hp, victory.end.player_mana)
+            ];
+            if replay {
+                lines.push(String::new());
+                lines.extend(victory.narrative);
+                lines.push(String::new());
+            }
+            lines.join("\n")
+        },
+        None => format!("{}: no way to win", part)
+    }
 }
 
-fn part1(boss: &Boss, player: &Player, next_spells: &NextSpellsFn) -> i32 {
-    least_mana(boss, player, next_spells, false)
+struct Options {
+    input: Option<String>,
+    spells: Option<String>,
+    player: Player,
+    replay: bool,
+    bench: Option<usize>,
+    search: Search,
+    play: bool,
+    hard: bool,
+    rules: Rules
 }
 
-fn part2(boss: &Boss, player: &Player, next_spells: &NextSpellsFn) -> i32 {
-    least_mana(boss, player, next_spells, true)
+impl Options {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Options {
+        let mut input = None;
+        let mut spells = None;
+        let mut player = Player { hp: 50, mana: 500, spent: 0 };
+        let mut replay = false;
+        let mut bench = None;
+        let mut search = Search::Dijkstra;
+        let mut play = false;
+        let mut hard = false;
+        let mut rules = Rules::ORIGINAL;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--replay" => replay = true,
+                "--spells" => spells = Some(args.next().expect("No spell file given")),
+                "--hp" => player.hp = args.next().and_then(|hp| hp.parse().ok()).expect("No valid player hp given"),
+                "--mana" => player.mana = args.next().and_then(|mana| mana.parse().ok()).expect("No valid player mana given"),
+                "--bench" => bench = Some(args.next().and_then(|n| n.parse().ok()).expect("No valid number of runs given")),
+                "--dfs" => search = Search::DepthFirst,
+                "--play" => play = true,
+                "--hard" => hard = true,
+                "--rules" => rules = args.next().expect("No rules given").parse().unwrap(),
+                _ => input = Some(arg)
+            }
+        }
+
+        Options { input, spells, player, replay, bench, search, play, hard, rules }
+    }
 }
 
 fn main() {
+    let options = Options::parse(env::args().skip(1));
+
+    if options.play {
+        let (spellbook, boss) = load(&options);
+        let stdin = io::stdin();
+        println!("{:?}", play(&boss, &options.player, &spellbook, &if options.hard { options.rules.hard() } else { options.rules }, stdin.lock()));
+        return;
+    }
+
+    if let Some(times) = options.bench {
+        let (spellbook, boss) = load(&options);
+        for search in [Search::DepthFirst, Search::Dijkstra] {
+            println!("{:?}:", search);
+            measure_times(times, || {
+                part1(&boss, &options.player, &spellbook, &default_next_spells, &options.rules, search);
+                part2(&boss, &options.player, &spellbook, &default_next_spells, &options.rules, search);
+            });
+        }
+        return;
+    }
+
     measure(|| {
-        let input = input().expect("Input failed");
-        let player = Player { hp: 50, mana: 500, spent: 0 };
-        println!("Part1: {}", part1(&input, &player, &default_next_spells));
-        println!("Part2: {}", part2(&input, &player, &default_next_spells));
+        let (spellbook, boss) = load(&options);
+        println!("{}", format_result("Part1", part1(&boss, &options.player, &spellbook, &default_next_spells, &options.rules, options.search), &spellbook, options.replay));
+        println!("{}", format_result("Part2", part2(&boss, &options.player, &spellbook, &default_next_spells, &options.rules, options.search), &spellbook, options.replay));
     });
 }
 
-fn input() -> io::Result<Input> {
-    let f = File::open(env::args().skip(1).next().expect("No input file given"))?;
+fn load(options: &Options) -> (Vec<Spell>, Boss) {
+    let (spellbook, boss) = match options.spells.as_ref() {
+        Some(path) => {
+            let book = spellbook(path).expect("Spell file failed");
+            (book.spells, book.boss)
+        },
+        None => (default_spells(), None)
+    };
+    let boss = boss.unwrap_or_else(|| input(options.input.as_ref().expect("No input file given")).expect("Input failed"));
+    (spellbook, boss)
+}
+
+fn input(path: &str) -> io::Result<Input> {
+    let f = File::open(path)?;
     let f = BufReader::new(f);
     Ok(Input::read(f))
 }
 
+fn spellbook(path: &str) -> io::Result<Spellbook> {
+    let f = File::open(path)?;
+    let f = BufReader::new(f);
+    Spellbook::read(f).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -304,7 +992,7 @@ mod tests {
             let spells_idx = vec![3, 0];
             vec![spells_idx[(step.turn / 2) as usize]]
         }
-        assert_eq!(least_mana(&boss, &player, &next_spells, false), 173 + 53);
+        assert_eq!(least_mana(&boss, &player, &default_spells(), &next_spells, &Rules::ORIGINAL).map(|v| v.mana), Some(173 + 53));
 
         #[cfg(feature = "print")] {
             println!("Test 2\n\n--\n");
@@ -314,6 +1002,252 @@ mod tests {
             let spells_idx = vec![4, 2, 1, 3, 0];
             vec![spells_idx[(step.turn / 2) as usize]]
         }
-        assert_eq!(least_mana(&boss, &player, &next_spells2, false), 229 + 113 + 73 + 173 + 53);
+        assert_eq!(least_mana(&boss, &player, &default_spells(), &next_spells2, &Rules::ORIGINAL).map(|v| v.mana), Some(229 + 113 + 73 + 173 + 53));
+    }
+
+    #[test]
+    fn test_replay() {
+        let boss = as_input(INPUT);
+        let player = Player { hp: 10, mana: 250, spent: 0 };
+        let victory = least_mana(&boss, &player, &default_spells(), &default_next_spells, &Rules::ORIGINAL).unwrap();
+        assert_eq!(victory.spells, vec![3, 0]);
+        assert_eq!(victory.narrative.join("\n"),
+"-- Player turn --
+- Player has 10 hit points, 0 armor, 250 mana
+- Boss has 13 hit points
+Player casts Poison.
+
+-- Boss turn --
+- Player has 10 hit points, 0 armor, 77 mana
+- Boss has 13 hit points
+Poison deals 3 damage; its timer is now 5.
+Boss attacks for 8 damage.
+
+-- Player turn --
+- Player has 2 hit points, 0 armor, 77 mana
+- Boss has 10 hit points
+Poison deals 3 damage; its timer is now 4.
+Player casts Magic Missile, dealing 4 damage.
+
+-- Boss turn --
+- Player has 2 hit points, 0 armor, 24 mana
+- Boss has 3 hit points
+Poison deals 3 damage; its timer is now 3.
+This kills the boss, and the player wins.");
+
+        let mut boss = boss;
+        boss.hp = 14;
+        let (turns, end, narrative) = replay(&boss, &player, &default_spells(), &[4, 2, 1, 3, 0], &Rules::ORIGINAL);
+        assert_eq!(turns.len(), 10);
+        assert_eq!(turns.iter().filter_map(|t| t.cast).collect::<Vec<_>>(), vec![4, 2, 1, 3, 0]);
+        assert_eq!(turns[3], TurnState { turn: 3, player_hp: 2, player_mana: 110, armor: 7, boss_hp: 14, effects: vec![(4, 3), (2, 6)], cast: None });
+        assert!(narrative.contains(&"Boss attacks for 8 - 7 = 1 damage!".to_string()));
+        assert!(narrative.contains(&"Player casts Drain, dealing 2 damage, and healing 2 hit points.".to_string()));
+        assert!(narrative.contains(&"Recharge wears off.".to_string()));
+        assert_eq!(turns[9], TurnState { turn: 9, player_hp: 1, player_mana: 114, armor: 0, boss_hp: 2, effects: vec![(3, 4)], cast: None });
+        assert_eq!(end, TurnState { turn: 9, player_hp: 1, player_mana: 114, armor: 0, boss_hp: -1, effects: vec![(3, 3)], cast: None });
+    }
+
+    #[test]
+    fn test_format_result() {
+        // Poison ticks 10 -> 7 on the boss turn and 7 -> 4 on the next player turn, Magic Missile
+        // then kills the boss. 250 - 173 - 53 leaves 24 mana, the boss hit once for 8.
+        let mut boss = as_input(INPUT);
+        boss.hp = 10;
+        let player = Player { hp: 10, mana: 250, spent: 0 };
+        let spellbook = default_spells();
+        let victory = least_mana(&boss, &player, &spellbook, &default_next_spells, &Rules::ORIGINAL);
+        assert_eq!(format_result("Part1", victory, &spellbook, false),
+                   "Part1: 226 (Poison, Magic Missile)\n  won after 3 turns with 2 hit points and 24 mana left");
+
+        // Magic Missile kills the boss right away, there's no boss turn after it
+        boss.hp = 4;
+        let victory = least_mana_dfs(&boss, &player, &spellbook, &default_next_spells, &Rules::ORIGINAL).unwrap();
+        assert_eq!((victory.turns.len(), victory.end.player_hp, victory.end.player_mana, victory.end.boss_hp), (1, 10, 197, 0));
+        assert!(victory.narrative.ends_with(&["Player casts Magic Missile, dealing 4 damage.".to_string(), "This kills the boss, and the player wins.".to_string()]));
+        assert_eq!(format_result("Part2", None, &spellbook, false), "Part2: no way to win");
+    }
+
+    fn as_spellbook(s: &str) -> Result<Spellbook, SpellsError> {
+        Spellbook::read(BufReader::new(s.as_bytes()))
+    }
+
+    #[test]
+    fn test_spellbook() {
+        let spellbook = as_spellbook(
+           "# A heavier boss and a spell that hurts over time while healing
+            [boss]
+            hp = 60
+            damage = 9
+
+            [[spell]]
+            name = \"Magic Missile\"
+            cost = 53
+            damage = 4
+
+            [[spell]]
+            name = \"Leech #2\"  # the name keeps its #
+            cost = 120
+            damage = 2
+            healing = 1
+            turns = 4").unwrap();
+        assert_eq!(spellbook.boss.map(|b| (b.hp, b.damage)), Some((60, 9)));
+        assert_eq!(spellbook.spells, vec![
+            Spell { name: "Magic Missile".to_string(), cost: 53, effects: vec![Effect { modifier: Damage, value: 4, turns: 0 }] },
+            Spell { name: "Leech #2".to_string(), cost: 120, effects: vec![
+                Effect { modifier: Damage, value: 2, turns: 4 }, Effect { modifier: Healing, value: 1, turns: 4 }
+            ] }
+        ]);
+
+        let error = |s: &str| as_spellbook(s).unwrap_err();
+        assert_eq!(error("cost = 5"), SpellsError { line: 1, message: "cost is outside of a [[spell]] or [boss] table".to_string() });
+        assert_eq!(error("[[spell]]\nname = \"A\"\ncost = x").line, 3);
+        assert_eq!(error("[[spell]]\nname = \"A\"\ncost = -5\nmana = 1"), SpellsError { line: 3, message: "cost can't be negative, got -5".to_string() });
+        assert_eq!(error("[[spell]]\nname = \"A\"\ncost = 1\nfire = 3").message, "unknown spell key fire");
+        assert_eq!(error("[[spell]]\nname = \"A\"\ncost = 1").message, "A has no effects");
+        assert_eq!(error("[[spell]]\nname = \"A\"\nmana = 1\nmana = 2").message, "spell mana is given twice");
+        assert_eq!(error("[[spell]]\nname = A").message, "name must be a quoted string, got 'A'");
+        assert_eq!(error("[[spell]]\nname = \"A\"\ncost = 1\nmana = 1\n[[spell]]\nname = \"A\"").message, "there is already a spell named A");
+        assert_eq!(error("[boss]\nhp = 10").message, "boss needs both hp and damage");
+        assert_eq!(error("[wizard]").message, "unknown section [wizard]");
+        assert_eq!(error("").message, "no spells given");
+
+        assert_eq!(as_spellbook(DEFAULT_SPELLS).unwrap().spells.len(), 5);
+    }
+
+    #[test]
+    fn test_more_spells() {
+        let boss = as_input(INPUT);
+        let player = Player { hp: 10, mana: 250, spent: 0 };
+        let mut spellbook = default_spells();
+        spellbook.push(Spell { name: "Smite".to_string(), cost: 100, effects: vec![Effect { modifier: Damage, value: 13, turns: 0 }] });
+        let victory = least_mana(&boss, &player, &spellbook, &default_next_spells, &Rules::ORIGINAL).unwrap();
+        assert_eq!((victory.mana, victory.spells), (100, vec![5]));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_dijkstra_matches_dfs() {
+        let spellbook = default_spells();
+        let player = Player { hp: 30, mana: 400, spent: 0 };
+        for hp in (10..40).step_by(3) {
+            for damage in 6..10 {
+                let boss = Boss { hp, damage };
+                for rules in [Rules::ORIGINAL, Rules::ORIGINAL.hard(), Rules::STRICT] {
+                    let expected = least_mana_dfs(&boss, &player, &spellbook, &default_next_spells, &rules).map(|v| v.mana);
+                    let victory = least_mana(&boss, &player, &spellbook, &default_next_spells, &rules);
+                    assert_eq!(victory.as_ref().map(|v| v.mana), expected, "boss {:?}, rules {:?}", boss, rules);
+                    if let Some(victory) = victory {
+                        assert_eq!(victory.spells.iter().map(|idx| spellbook[*idx].cost).sum::<i32>(), victory.mana);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_play() {
+        let boss = as_input(INPUT);
+        let player = Player { hp: 10, mana: 250, spent: 0 };
+        let spellbook = default_spells();
+        let commands = "hint\nfireball\n4\n\nshield\nmagic missile\n";
+        assert_eq!(play(&boss, &player, &spellbook, &Rules::ORIGINAL, commands.as_bytes()), PlayOutcome::Won { spent: 173 + 53 });
+        assert_eq!(play(&boss, &player, &spellbook, &Rules::ORIGINAL, "1\nq\n".as_bytes()), PlayOutcome::Quit);
+        assert_eq!(play(&boss, &player, &spellbook, &Rules::ORIGINAL, "1\n1\n".as_bytes()), PlayOutcome::Lost);
+        assert_eq!(play(&boss, &player, &spellbook, &Rules::ORIGINAL.hard(), "4\n1\n".as_bytes()), PlayOutcome::Lost);
+    }
+
+    #[test]
+    fn test_cheapest_win_from_state() {
+        let boss = as_input(INPUT);
+        let player = Player { hp: 10, mana: 250, spent: 0 };
+        let spellbook = default_spells();
+        let mut step = Step::new(&boss, &player, &spellbook, &Rules::ORIGINAL).cast(0);
+        step.run_battle();
+        step.turn += 1;
+        assert_eq!((step.player.hp, step.boss.hp), (2, 9));
+        assert_eq!(cheapest_win(step, &default_next_spells), None);
+
+        let mut step = Step::new(&boss, &player, &spellbook, &Rules::ORIGINAL).cast(3);
+        step.run_battle();
+        step.turn += 1;
+        assert_eq!(cheapest_win(step.clone(), &default_next_spells), Some((173 + 53, vec![3, 0])));
+        step.rules = Rules::ORIGINAL.hard();
+        assert_eq!(cheapest_win(step, &default_next_spells), Some((173 + 53, vec![3, 0])));
+
+        // The same situation on the boss's turn is a different battle, two turns later it isn't
+        let step = Step::new(&boss, &player, &spellbook, &Rules::ORIGINAL.hard());
+        let boss_turn = Step { turn: 1, ..step.clone() };
+        let later = Step { turn: 2, ..step.clone() };
+        assert!(step.key() != boss_turn.key());
+        assert!(step.key() == later.key());
+    }
+
+    #[test]
+    fn test_rule_affordability() {
+        let boss = as_input(INPUT);
+        let spellbook = default_spells();
+        let player = Player { hp: 10, mana: 53, spent: 0 };
+        let rules = Rules { affordability: Affordability::UpToMana, ..Rules::ORIGINAL };
+        assert_eq!(default_next_spells(&Step::new(&boss, &player, &spellbook, &Rules::ORIGINAL)), vec![]);
+        assert_eq!(default_next_spells(&Step::new(&boss, &player, &spellbook, &rules)), vec![0]);
+    }
+
+    #[test]
+    fn test_rule_out_of_mana() {
+        let spellbook = default_spells();
+        let player = Player { hp: 10, mana: 53, spent: 0 };
+        let rules = Rules { affordability: Affordability::UpToMana, out_of_mana_loses: false, ..Rules::ORIGINAL };
+        let boss = Boss { hp: 4, damage: 8 };
+
+        let step = Step::new(&boss, &player, &spellbook, &Rules { out_of_mana_loses: true, ..rules }).cast(0);
+        assert!(step.finished() && !step.player_wins());
+        let step = Step::new(&boss, &player, &spellbook, &rules).cast(0);
+        assert!(step.finished() && step.player_wins());
+
+        let boss = Boss { hp: 5, damage: 8 };
+        let step = Step::new(&boss, &player, &spellbook, &rules).cast(0);
+        assert!(!step.finished());
+        assert_eq!(least_mana(&boss, &player, &spellbook, &default_next_spells, &rules).map(|v| v.mana), None);
+    }
+
+    #[test]
+    fn test_rule_drain() {
+        let boss = as_input(INPUT);
+        let spellbook = default_spells();
+        let player = Player { hp: 10, mana: 250, spent: 0 };
+        let mut step = Step::new(&boss, &player, &spellbook, &Rules { drain: 3, ..Rules::ORIGINAL });
+        step.drain();
+        assert_eq!(step.player.hp, 7);
+        step.turn += 1;
+        step.drain();
+        assert_eq!(step.player.hp, 7);
+        assert_eq!(Rules::STRICT.hard().drain, 1);
+        assert_eq!(least_mana(&boss, &player, &spellbook, &default_next_spells, &Rules { drain: 9, ..Rules::ORIGINAL }).map(|v| v.mana), None);
+    }
+
+    #[test]
+    fn test_rule_min_damage() {
+        let boss = Boss { hp: 50, damage: 5 };
+        let spellbook = default_spells();
+        let player = Player { hp: 10, mana: 250, spent: 0 };
+        let shielded = |rules: &Rules| {
+            let mut step = Step::new(&boss, &player, &spellbook, rules).cast(2);
+            step.run_battle();
+            step.player.hp
+        };
+        assert_eq!(shielded(&Rules::ORIGINAL), 12);
+        assert_eq!(shielded(&Rules { min_damage: Some(0), ..Rules::ORIGINAL }), 10);
+        assert_eq!(shielded(&Rules::STRICT), 9);
+    }
+
+    #[test]
+    fn test_rule_presets() {
+        assert_eq!("original".parse::<Rules>(), Ok(Rules::ORIGINAL));
+        assert_eq!("strict".parse::<Rules>(), Ok(Rules::STRICT));
+        assert!("lenient".parse::<Rules>().is_err());
+
+        let boss = as_input(INPUT);
+        let player = Player { hp: 10, mana: 250, spent: 0 };
+        assert_eq!(least_mana(&boss, &player, &default_spells(), &default_next_spells, &Rules::STRICT).map(|v| v.mana), Some(173 + 53));
+    }
+}
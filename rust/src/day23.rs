@@ -1,8 +1,12 @@
 extern crate utils;
 
 use std::env;
+use std::cmp;
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::error;
 use std::fmt;
+use std::hash::Hash;
 use std::str::FromStr;
 use std::io::{self, BufReader};
 use std::io::prelude::*;
@@ -11,20 +15,167 @@ use utils::*;
 
 type Input = Program;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 struct Program {
-    instructions: Vec<Instruction>
+    instructions: Vec<Instruction>,
+    registers: Vec<String>
 }
 
 impl Program {
-    fn read<R: Read>(reader: BufReader<R>) -> Program {
-        Program {
-            instructions: reader.lines().map(|l| l.unwrap().trim().parse::<Instruction>().unwrap()).collect()
+    fn read<R: Read>(mut reader: BufReader<R>) -> Result<Program, AssembleError> {
+        let mut source = String::new();
+        reader.read_to_string(&mut source).map_err(|e| AssembleError { line: 0, kind: AssembleErrorKind::Io(e.to_string()) })?;
+        Program::assemble(&source)
+    }
+
+    // Labels are defined as `name:` either on a line of their own or before an instruction,
+    // everything after `;` or `#` is a comment. Registers are numbered in order of appearance.
+    fn assemble(source: &str) -> Result<Program, AssembleError> {
+        let mut labels = HashMap::new();
+        let mut lines = vec![];
+
+        for (line_nr, line) in source.lines().enumerate().map(|(i, l)| (i + 1, l)) {
+            let mut line = line.split(&[';', '#'][..]).next().unwrap().trim();
+
+            while let Some(colon) = line.find(':') {
+                let label = line[0..colon].trim();
+                let error = |kind| AssembleError { line: line_nr, kind };
+                if !is_identifier(label) {
+                    return Err(error(AssembleErrorKind::InvalidLabel(label.into())));
+                }
+                if labels.insert(label, lines.len()).is_some() {
+                    return Err(error(AssembleErrorKind::DuplicateLabel(label.into())));
+                }
+                line = line[colon + 1..].trim();
+            }
+
+            if !line.is_empty() {
+                lines.push((line_nr, line));
+            }
+        }
+
+        let mut registers = vec![];
+        let mut instructions = Vec::with_capacity(lines.len());
+        for (pc, (line_nr, line)) in lines.iter().enumerate() {
+            let resolve = |label: &str| labels.get(label).map(|target| *target as i32 - pc as i32);
+            let instruction = parse_instruction(line, &resolve, &mut registers).map_err(|kind| AssembleError { line: *line_nr, kind })?;
+            instructions.push(instruction);
+        }
+
+        Ok(Program { instructions, registers })
+    }
+
+    // Jump targets within the program, or just past its end, get labels while targets outside
+    // of that are kept as relative offsets
+    fn disassemble(&self) -> String {
+        let len = self.instructions.len();
+        let mut targets: Vec<usize> = self.instructions.iter().enumerate()
+            .filter_map(|(pc, instruction)| instruction.offset().map(|o| pc as i64 + o as i64))
+            .filter(|target| *target >= 0 && *target <= len as i64)
+            .map(|target| target as usize)
+            .collect();
+        targets.sort();
+        targets.dedup();
+
+        let label = |pc: usize| targets.binary_search(&pc).ok().map(|i| format!("l{}", i));
+
+        let mut text = String::new();
+        for pc in 0..=len {
+            if let Some(label) = label(pc) {
+                text.push_str(&format!("{}:\n", label));
+            }
+
+            if let Some(instruction) = self.instructions.get(pc) {
+                let target = instruction.offset().and_then(|o| {
+                    let target = pc as i64 + o as i64;
+                    if target >= 0 { label(target as usize) } else { None }
+                });
+                text.push_str(&format!("    {}\n", self.format_instruction(instruction, target)));
+            }
+        }
+        text
+    }
+
+    fn format_instruction(&self, instruction: &Instruction, target: Option<String>) -> String {
+        use Instruction::*;
+
+        let name = |r: &Register| &self.registers[r_to_i(r)];
+        let offset = |o: &i32| target.clone().unwrap_or_else(|| format!("{:+}", o));
+
+        match instruction {
+            Hlf(r) | Tpl(r) | Inc(r) => format!("{} {}", instruction.kind(), name(r)),
+            Jmp(o) => format!("{} {}", instruction.kind(), offset(o)),
+            Jie(r, o) | Jio(r, o) => format!("{} {}, {}", instruction.kind(), name(r), offset(o))
         }
     }
+
+    fn format_state<W: Word>(&self, pc: usize, registers: &[W]) -> String {
+        let registers: Vec<_> = self.registers.iter().zip(registers).map(|(name, value)| format!("{}={}", name, value)).collect();
+        match self.instructions.get(pc) {
+            Some(instruction) => format!("{:4}  {:<12} {}", pc, self.format_instruction(instruction, None), registers.join(" ")),
+            None => format!("halted at {}  {}", pc, registers.join(" "))
+        }
+    }
+
+    fn register(&self, name: &str) -> Option<Register> {
+        self.registers.iter().position(|r| r == name).map(Register)
+    }
 }
 
-#[derive(Clone, Debug)]
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        _ => false
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum AssembleErrorKind {
+    Io(String),
+    UnknownInstruction(String),
+    InvalidRegister(String),
+    InvalidOffset(String),
+    InvalidLabel(String),
+    UndefinedLabel(String),
+    DuplicateLabel(String),
+    WrongOperandCount(Kind, usize)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssembleError {
+    line: usize,
+    kind: AssembleErrorKind
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use AssembleErrorKind::*;
+
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            Io(e) => write!(f, "{}", e),
+            UnknownInstruction(name) => write!(f, "unknown instruction '{}'", name),
+            InvalidRegister(r) => write!(f, "invalid register '{}'", r),
+            InvalidOffset(o) => write!(f, "invalid offset '{}'", o),
+            InvalidLabel(l) => write!(f, "invalid label '{}'", l),
+            UndefinedLabel(l) => write!(f, "undefined label '{}'", l),
+            DuplicateLabel(l) => write!(f, "duplicate label '{}'", l),
+            WrongOperandCount(kind, n) => write!(f, "{} takes {} operand(s), got {}", kind, kind.operands(), n)
+        }
+    }
+}
+
+impl error::Error for AssembleError {
+    fn description(&self) -> &str {
+        "unable to assemble program"
+    }
+    fn cause(&self) -> Option<&dyn error::Error> {
+        None
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 enum Instruction {
     Hlf(Register),
     Tpl(Register),
@@ -34,6 +185,73 @@ enum Instruction {
     Jio(Register, i32)
 }
 
+impl Instruction {
+    fn kind(&self) -> Kind {
+        use Instruction::*;
+
+        match self {
+            Hlf(_) => Kind::Hlf,
+            Tpl(_) => Kind::Tpl,
+            Inc(_) => Kind::Inc,
+            Jmp(_) => Kind::Jmp,
+            Jie(_, _) => Kind::Jie,
+            Jio(_, _) => Kind::Jio
+        }
+    }
+
+    fn offset(&self) -> Option<i32> {
+        use Instruction::*;
+
+        match self {
+            Jmp(o) | Jie(_, o) | Jio(_, o) => Some(*o),
+            _ => None
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    Hlf, Tpl, Inc, Jmp, Jie, Jio
+}
+
+impl Kind {
+    fn operands(&self) -> usize {
+        match self {
+            Kind::Jie | Kind::Jio => 2,
+            _ => 1
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Kind::Hlf => "hlf",
+            Kind::Tpl => "tpl",
+            Kind::Inc => "inc",
+            Kind::Jmp => "jmp",
+            Kind::Jie => "jie",
+            Kind::Jio => "jio"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Kind {
+    type Err = InstructionParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hlf" => Ok(Kind::Hlf),
+            "tpl" => Ok(Kind::Tpl),
+            "inc" => Ok(Kind::Inc),
+            "jmp" => Ok(Kind::Jmp),
+            "jie" => Ok(Kind::Jie),
+            "jio" => Ok(Kind::Jio),
+            _ => Err(InstructionParseError {})
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct InstructionParseError;
 impl fmt::Display for InstructionParseError {
@@ -45,138 +263,980 @@ impl error::Error for InstructionParseError {
     fn description(&self) -> &str {
         "unable to parse instruction"
     }
-    fn cause(&self) -> Option<&error::Error> {
+    fn cause(&self) -> Option<&dyn error::Error> {
         None
     }
 }
 
-impl FromStr for Instruction {
-    type Err = InstructionParseError;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        use Instruction::*;
+fn parse_instruction(s: &str, resolve: &dyn Fn(&str) -> Option<i32>, registers: &mut Vec<String>) -> Result<Instruction, AssembleErrorKind> {
+    use Instruction::*;
 
-        let name = &s[0..3];
-        let args: Vec<_> = (&s[3..]).split(',').map(|a| a.trim()).collect();
+    let (name, args) = match s.find(char::is_whitespace) {
+        Some(i) => (&s[0..i], s[i..].split(',').map(|a| a.trim()).collect()),
+        None => (s, vec![])
+    };
+
+    let kind = name.parse::<Kind>().map_err(|_| AssembleErrorKind::UnknownInstruction(name.into()))?;
+    if args.len() != kind.operands() {
+        return Err(AssembleErrorKind::WrongOperandCount(kind, args.len()));
+    }
 
-        fn to_rgister(r: &str) -> Register {
-            match r {
-                "a" => Register::A,
-                "b" => Register::B,
-                _ => unreachable!()
+    let mut register = |r: &str| {
+        if !is_identifier(r) {
+            return Err(AssembleErrorKind::InvalidRegister(r.into()));
+        }
+        match registers.iter().position(|name| name == r) {
+            Some(i) => Ok(Register(i)),
+            None => {
+                registers.push(r.into());
+                Ok(Register(registers.len() - 1))
             }
         }
-
-        fn offset(o: &str) -> i32 {
-            o.parse().unwrap()
+    };
+    let offset = |o: &str| {
+        if o.starts_with(|c: char| c == '+' || c == '-' || c.is_ascii_digit()) {
+            o.parse::<i32>().map_err(|_| AssembleErrorKind::InvalidOffset(o.into()))
+        } else if is_identifier(o) {
+            resolve(o).ok_or_else(|| AssembleErrorKind::UndefinedLabel(o.into()))
+        } else {
+            Err(AssembleErrorKind::InvalidOffset(o.into()))
         }
+    };
 
-        match name {
-            "hlf" => Ok(Hlf(to_rgister(args[0]))),
-            "tpl" => Ok(Tpl(to_rgister(args[0]))),
-            "inc" => Ok(Inc(to_rgister(args[0]))),
-            "jmp" => Ok(Jmp(offset(args[0]))),
-            "jie" => Ok(Jie(to_rgister(args[0]), offset(args[1]))),
-            "jio" => Ok(Jio(to_rgister(args[0]), offset(args[1]))),
-            _ => Err(InstructionParseError {})
+    Ok(match kind {
+        Kind::Hlf => Hlf(register(args[0])?),
+        Kind::Tpl => Tpl(register(args[0])?),
+        Kind::Inc => Inc(register(args[0])?),
+        Kind::Jmp => Jmp(offset(args[0])?),
+        Kind::Jie => Jie(register(args[0])?, offset(args[1])?),
+        Kind::Jio => Jio(register(args[0])?, offset(args[1])?)
+    })
+}
+
+// Index into the register file, the names are kept by the program
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Register(usize);
+
+fn r_to_i(r: &Register) -> usize {
+    r.0
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Overflow {
+    Wrap, Saturate, Trap
+}
+
+impl FromStr for Overflow {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wrap" => Ok(Overflow::Wrap),
+            "saturate" => Ok(Overflow::Saturate),
+            "trap" => Ok(Overflow::Trap),
+            _ => Err(format!("unknown overflow behaviour '{}'", s))
         }
     }
 }
 
-#[derive(Clone, Debug)]
-enum Register {
-    A, B
+// Register values are non-negative like in the puzzle, the arithmetic returns false when
+// trapping on an overflow and the value is then left unchanged
+trait Word: Clone + Eq + Hash + fmt::Debug + fmt::Display + FromStr {
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn halve(&mut self);
+    fn triple(&mut self, overflow: Overflow) -> bool;
+    fn increment(&mut self, overflow: Overflow) -> bool;
+    fn is_even(&self) -> bool;
+    fn is_one(&self) -> bool;
+}
+
+macro_rules! fixed_width_word {
+    ($($t:ty),*) => {
+        $(
+            impl Word for $t {
+                fn zero() -> Self {
+                    0
+                }
+
+                fn one() -> Self {
+                    1
+                }
+
+                fn halve(&mut self) {
+                    *self /= 2;
+                }
+
+                fn triple(&mut self, overflow: Overflow) -> bool {
+                    match overflow {
+                        Overflow::Wrap => *self = self.wrapping_mul(3),
+                        Overflow::Saturate => *self = self.saturating_mul(3),
+                        Overflow::Trap => match self.checked_mul(3) {
+                            Some(v) => *self = v,
+                            None => return false
+                        }
+                    }
+                    true
+                }
+
+                fn increment(&mut self, overflow: Overflow) -> bool {
+                    match overflow {
+                        Overflow::Wrap => *self = self.wrapping_add(1),
+                        Overflow::Saturate => *self = self.saturating_add(1),
+                        Overflow::Trap => match self.checked_add(1) {
+                            Some(v) => *self = v,
+                            None => return false
+                        }
+                    }
+                    true
+                }
+
+                fn is_even(&self) -> bool {
+                    *self % 2 == 0
+                }
+
+                fn is_one(&self) -> bool {
+                    *self == 1
+                }
+            }
+        )*
+    }
+}
+
+fixed_width_word!(u8, u16, u32, u64, u128);
+
+impl Word for BigUint {
+    fn zero() -> Self {
+        BigUint::zero()
+    }
+
+    fn one() -> Self {
+        BigUint::from(1)
+    }
+
+    fn halve(&mut self) {
+        self.div_small(2);
+    }
+
+    fn triple(&mut self, _: Overflow) -> bool {
+        self.mul_add_small(3, 0);
+        true
+    }
+
+    fn increment(&mut self, _: Overflow) -> bool {
+        self.mul_add_small(1, 1);
+        true
+    }
+
+    fn is_even(&self) -> bool {
+        BigUint::is_even(self)
+    }
+
+    fn is_one(&self) -> bool {
+        *self == BigUint::from(1)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Width {
+    U8, U16, U32, U64, U128, Big
+}
+
+impl FromStr for Width {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "8" => Ok(Width::U8),
+            "16" => Ok(Width::U16),
+            "32" => Ok(Width::U32),
+            "64" => Ok(Width::U64),
+            "128" => Ok(Width::U128),
+            "big" => Ok(Width::Big),
+            _ => Err(format!("unknown width '{}'", s))
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
-struct Computer {
-    registers: [i32; 2],
+struct Computer<W: Word> {
+    registers: Vec<W>,
+    overflow: Overflow,
     pc: usize
 }
 
-fn r_to_i(r: &Register) -> usize {
-    match r {
-        Register::A => 0,
-        Register::B => 1,
+impl<W: Word> Computer<W> {
+    fn new(program: &Program, overflow: Overflow) -> Computer<W> {
+        Computer { registers: vec![W::zero(); program.registers.len()], overflow, pc: 0 }
+    }
+
+    fn run(&mut self, program: &Program, limits: &Limits) -> RunOutcome {
+        self.run_with(program, limits, |_, _| {})
+    }
+
+    // Same as run but calls on_step with the pc of every executed instruction and the pc after it
+    fn run_with<F: FnMut(usize, usize)>(&mut self, program: &Program, limits: &Limits, mut on_step: F) -> RunOutcome {
+        let mut watchdog = Watchdog::new(limits);
+        loop {
+            if self.halted(program) {
+                return RunOutcome::Halted;
+            }
+            if let Err(outcome) = watchdog.check(self) {
+                return outcome;
+            }
+            let pc = self.pc;
+            if let Err(outcome) = self.step(program) {
+                return outcome;
+            }
+            on_step(pc, self.pc);
+        }
     }
-}
 
-impl Computer {
-    fn new() -> Computer {
-        Computer { registers: [0, 0], pc: 0 }
+    fn halted(&self, program: &Program) -> bool {
+        self.pc >= program.instructions.len()
     }
 
-    fn run(&mut self, program: &Program) {
+    // A jump outside of the program leaves pc at the jump instruction, landing just past the
+    // last instruction is a regular halt
+    fn step(&mut self, program: &Program) -> Result<(), RunOutcome> {
         use Instruction::*;
 
-        while self.pc < program.instructions.len() {
-            match &program.instructions[self.pc] {
-                Hlf(r) => {
-                    self.registers[r_to_i(r)] /= 2;
-                    self.pc += 1;
-                },
-                Tpl(r) => {
-                    self.registers[r_to_i(r)] *= 3;
+        if self.halted(program) {
+            return Err(RunOutcome::Halted);
+        }
+
+        match &program.instructions[self.pc] {
+            Hlf(r) => {
+                self.registers[r_to_i(r)].halve();
+                self.pc += 1;
+            },
+            Tpl(r) => {
+                if !self.registers[r_to_i(r)].triple(self.overflow) {
+                    return Err(RunOutcome::Overflow { pc: self.pc });
+                }
+                self.pc += 1;
+            },
+            Inc(r) => {
+                if !self.registers[r_to_i(r)].increment(self.overflow) {
+                    return Err(RunOutcome::Overflow { pc: self.pc });
+                }
+                self.pc += 1;
+            },
+            Jmp(o) => {
+                self.jump(*o, program)?;
+            },
+            Jie(r, o) => {
+                if self.registers[r_to_i(r)].is_even() {
+                    self.jump(*o, program)?;
+                } else {
                     self.pc += 1;
-                },
-                Inc(r) => {
-                    self.registers[r_to_i(r)] += 1;
+                }
+            },
+            Jio(r, o) => {
+                if self.registers[r_to_i(r)].is_one() {
+                    self.jump(*o, program)?;
+                } else {
                     self.pc += 1;
-                },
-                Jmp(o) => {
-                    self.pc = (self.pc as i32 + o) as usize;
-                },
-                Jie(r, o) => {
-                    if self.registers[r_to_i(r)] % 2 == 0 {
-                        self.pc = (self.pc as i32 + o) as usize;
-                    } else {
-                        self.pc += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn jump(&mut self, offset: i32, program: &Program) -> Result<(), RunOutcome> {
+        let target = self.pc as i64 + offset as i64;
+        if target < 0 || target > program.instructions.len() as i64 {
+            return Err(RunOutcome::JumpedOutOfBounds { pc: self.pc, target });
+        }
+        self.pc = target as usize;
+        Ok(())
+    }
+
+    fn set_register(&mut self, register: Register, value: W) {
+        self.registers[r_to_i(&register)] = value;
+    }
+
+    fn peek_register(&self, register: Register) -> W {
+        self.registers[r_to_i(&register)].clone()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum RunOutcome {
+    Halted,
+    JumpedOutOfBounds { pc: usize, target: i64 },
+    BudgetExceeded { steps: u64 },
+    LoopDetected { pc: usize, steps: u64 },
+    Overflow { pc: usize }
+}
+
+impl RunOutcome {
+    // Both ways of leaving the program are regular exits according to the puzzle
+    fn terminated(&self) -> bool {
+        matches!(self, RunOutcome::Halted | RunOutcome::JumpedOutOfBounds { .. })
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct Limits {
+    budget: Option<u64>,
+    detect_loops: bool
+}
+
+struct Watchdog<W: Word> {
+    limits: Limits,
+    steps: u64,
+    last_pc: usize,
+    seen: HashSet<(usize, Vec<W>)>
+}
+
+impl<W: Word> Watchdog<W> {
+    fn new(limits: &Limits) -> Watchdog<W> {
+        Watchdog { limits: limits.clone(), steps: 0, last_pc: 0, seen: HashSet::new() }
+    }
+
+    // Called before every executed instruction. Every cycle has to pass a backward jump so only
+    // the states right after one of those needs to be remembered for detecting revisits.
+    fn check(&mut self, computer: &Computer<W>) -> Result<(), RunOutcome> {
+        self.check_state(computer.pc, &computer.registers)
+    }
+
+    fn check_state(&mut self, pc: usize, registers: &[W]) -> Result<(), RunOutcome> {
+        if let Some(budget) = self.limits.budget {
+            if self.steps >= budget {
+                return Err(RunOutcome::BudgetExceeded { steps: self.steps });
+            }
+        }
+
+        let after_backward_jump = self.steps > 0 && pc <= self.last_pc;
+        if self.limits.detect_loops && after_backward_jump && !self.seen.insert((pc, registers.to_vec())) {
+            return Err(RunOutcome::LoopDetected { pc, steps: self.steps });
+        }
+
+        self.last_pc = pc;
+        self.steps += 1;
+        Ok(())
+    }
+}
+
+type Handler<W> = Box<dyn Fn(&mut [W]) -> usize>;
+
+// Straight-line arithmetic inside a block with the exit to take when it overflows
+#[derive(Clone, Copy)]
+enum Op {
+    Hlf(usize), Tpl(usize, usize), Inc(usize, usize)
+}
+
+// How a block ends, falling off the end of the program counts as a jmp to past the last instruction
+#[derive(Clone, Copy)]
+enum Branch {
+    Jmp(usize), Jie(usize, usize, usize), Jio(usize, usize, usize)
+}
+
+// The program lowered to closures with register indices and overflow behaviour resolved and jump
+// targets made absolute. Every handler returns the pc to continue at, anything past the last
+// instruction indexes the exits which are also resolved up front. There is a handler per
+// instruction for runs that need to be counted by the watchdog and a block per pc that runs
+// everything up to and including the next jump in a single call for runs without limits.
+struct CompiledProgram<W: Word> {
+    handlers: Vec<Handler<W>>,
+    blocks: Vec<Handler<W>>,
+    exits: Vec<(usize, RunOutcome)>
+}
+
+impl<W: Word + 'static> CompiledProgram<W> {
+    fn compile(program: &Program, overflow: Overflow) -> CompiledProgram<W> {
+        use Instruction::*;
+
+        let len = program.instructions.len();
+        let mut exits = vec![(len, RunOutcome::Halted)];
+        let mut exit = |pc: usize, outcome: RunOutcome| {
+            exits.push((pc, outcome));
+            len + exits.len() - 1
+        };
+
+        // The trap exit for tpl and inc and the target for jumps, unused for hlf
+        let resolved: Vec<usize> = program.instructions.iter().enumerate().map(|(pc, instruction)| match *instruction {
+            Hlf(_) => 0,
+            Tpl(_) | Inc(_) => exit(pc, RunOutcome::Overflow { pc }),
+            Jmp(o) | Jie(_, o) | Jio(_, o) => {
+                let target = pc as i64 + o as i64;
+                if target < 0 || target > len as i64 {
+                    exit(pc, RunOutcome::JumpedOutOfBounds { pc, target })
+                } else {
+                    target as usize
+                }
+            }
+        }).collect();
+
+        let mut handlers: Vec<Handler<W>> = Vec::with_capacity(len);
+        for (pc, instruction) in program.instructions.iter().enumerate() {
+            let next = pc + 1;
+            let resolved = resolved[pc];
+            handlers.push(match *instruction {
+                Hlf(Register(r)) => Box::new(move |registers: &mut [W]| {
+                    registers[r].halve();
+                    next
+                }),
+                Tpl(Register(r)) => Box::new(move |registers: &mut [W]| if registers[r].triple(overflow) { next } else { resolved }),
+                Inc(Register(r)) => Box::new(move |registers: &mut [W]| if registers[r].increment(overflow) { next } else { resolved }),
+                Jmp(_) => Box::new(move |_: &mut [W]| resolved),
+                Jie(Register(r), _) => Box::new(move |registers: &mut [W]| if registers[r].is_even() { resolved } else { next }),
+                Jio(Register(r), _) => Box::new(move |registers: &mut [W]| if registers[r].is_one() { resolved } else { next })
+            });
+        }
+
+        // Chains of jmp are skipped when continuing after a block, a cycle of them is left as is
+        let follow = |mut pc: usize| {
+            for _ in 0..len {
+                match program.instructions.get(pc) {
+                    Some(Jmp(_)) => pc = resolved[pc],
+                    _ => break
+                }
+            }
+            pc
+        };
+
+        let mut blocks: Vec<Handler<W>> = Vec::with_capacity(len);
+        for start in 0..len {
+            let mut ops = vec![];
+            let mut pc = start;
+            let branch = loop {
+                match program.instructions.get(pc) {
+                    Some(Hlf(Register(r))) => ops.push(Op::Hlf(*r)),
+                    Some(Tpl(Register(r))) => ops.push(Op::Tpl(*r, resolved[pc])),
+                    Some(Inc(Register(r))) => ops.push(Op::Inc(*r, resolved[pc])),
+                    Some(Jmp(_)) => break Branch::Jmp(follow(resolved[pc])),
+                    Some(Jie(Register(r), _)) => break Branch::Jie(*r, follow(resolved[pc]), follow(pc + 1)),
+                    Some(Jio(Register(r), _)) => break Branch::Jio(*r, follow(resolved[pc]), follow(pc + 1)),
+                    None => break Branch::Jmp(pc)
+                }
+                pc += 1;
+            };
+
+            // A block that continues at its own start keeps looping without returning
+            blocks.push(Box::new(move |registers: &mut [W]| loop {
+                for op in ops.iter() {
+                    match *op {
+                        Op::Hlf(r) => registers[r].halve(),
+                        Op::Tpl(r, trap) => if !registers[r].triple(overflow) { return trap; },
+                        Op::Inc(r, trap) => if !registers[r].increment(overflow) { return trap; }
                     }
-                },
-                Jio(r, o) => {
-                    if self.registers[r_to_i(r)] == 1 {
-                        self.pc = (self.pc as i32 + o) as usize;
-                    } else {
-                        self.pc += 1;
+                }
+                let next = match branch {
+                    Branch::Jmp(target) => target,
+                    Branch::Jie(r, target, next) => if registers[r].is_even() { target } else { next },
+                    Branch::Jio(r, target, next) => if registers[r].is_one() { target } else { next }
+                };
+                if next != start {
+                    return next;
+                }
+            }));
+        }
+
+        CompiledProgram { handlers, blocks, exits }
+    }
+
+    // Behaves exactly like Computer::run, the watchdog is only involved when there are limits
+    fn run(&self, computer: &mut Computer<W>, limits: &Limits) -> RunOutcome {
+        let handlers = &self.handlers;
+        let registers = &mut computer.registers[..];
+        let mut pc = computer.pc;
+
+        // A computer that was already past the end when started has simply halted, pc only gets
+        // past the handlers through one of the exits otherwise
+        if pc >= handlers.len() {
+            return RunOutcome::Halted;
+        }
+
+        if limits.budget.is_none() && !limits.detect_loops {
+            while let Some(block) = self.blocks.get(pc) {
+                pc = block(registers);
+            }
+        } else {
+            let mut watchdog = Watchdog::new(limits);
+            while let Some(handler) = handlers.get(pc) {
+                if let Err(outcome) = watchdog.check_state(pc, registers) {
+                    computer.pc = pc;
+                    return outcome;
+                }
+                pc = handler(registers);
+            }
+        }
+
+        let (stop_pc, outcome) = self.exits[pc - handlers.len()].clone();
+        computer.pc = stop_pc;
+        outcome
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Interpreter {
+    Reference, Compiled
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct HotLoop {
+    start: usize,
+    end: usize,
+    iterations: u64,
+    steps: u64
+}
+
+struct Profile {
+    steps: u64,
+    pc_counts: Vec<u64>,
+    back_edges: HashMap<(usize, usize), u64>
+}
+
+impl Profile {
+    fn new(program: &Program) -> Profile {
+        Profile { steps: 0, pc_counts: vec![0; program.instructions.len()], back_edges: HashMap::new() }
+    }
+
+    fn record(&mut self, pc: usize, next_pc: usize) {
+        self.steps += 1;
+        self.pc_counts[pc] += 1;
+        if next_pc <= pc {
+            *self.back_edges.entry((pc, next_pc)).or_insert(0) += 1;
+        }
+    }
+
+    fn kind_counts(&self, program: &Program) -> Vec<(Kind, u64)> {
+        let mut counts: Vec<(Kind, u64)> = vec![];
+        for (instruction, count) in program.instructions.iter().zip(&self.pc_counts) {
+            match counts.iter_mut().find(|(kind, _)| *kind == instruction.kind()) {
+                Some((_, c)) => *c += count,
+                None => counts.push((instruction.kind(), *count))
+            }
+        }
+        counts.sort_by_key(|(_, count)| cmp::Reverse(*count));
+        counts
+    }
+
+    // Every taken backward jump closes a loop spanning from its target to the jump itself, the
+    // number of times it's taken is the number of iterations that started over
+    fn hot_loops(&self) -> Vec<HotLoop> {
+        let mut loops: Vec<_> = self.back_edges.iter()
+            .map(|(&(end, start), &iterations)| {
+                HotLoop { start, end, iterations, steps: self.pc_counts[start..=end].iter().sum() }
+            })
+            .collect();
+        loops.sort_by(|a, b| b.steps.cmp(&a.steps).then(a.start.cmp(&b.start)));
+        loops
+    }
+
+    fn report(&self, program: &Program) -> String {
+        let percent = |count: u64| if self.steps > 0 { count as f64 * 100.0 / self.steps as f64 } else { 0.0 };
+        let mut report = format!("Executed {} instructions\n", self.steps);
+
+        report.push_str("By instruction kind:\n");
+        for (kind, count) in self.kind_counts(program) {
+            report.push_str(&format!("  {}  {:>10}  {:5.1}%\n", kind, count, percent(count)));
+        }
+
+        report.push_str("By pc:\n");
+        for (pc, (instruction, count)) in program.instructions.iter().zip(&self.pc_counts).enumerate() {
+            report.push_str(&format!("  {:4}  {:<12} {:>10}  {:5.1}%\n", pc, program.format_instruction(instruction, None), count, percent(*count)));
+        }
+
+        report.push_str("Hot loops:\n");
+        for hot_loop in self.hot_loops().iter().take(5) {
+            report.push_str(&format!("  {:4}..={:<4} {:>10} iterations {:>10} instructions  {:5.1}%\n",
+                hot_loop.start, hot_loop.end, hot_loop.iterations, hot_loop.steps, percent(hot_loop.steps)));
+        }
+        report
+    }
+}
+
+fn profile<W: Word>(mut computer: Computer<W>, program: &Program, limits: &Limits) -> (RunOutcome, Profile) {
+    let mut profile = Profile::new(program);
+    let outcome = computer.run_with(program, limits, |pc, next_pc| profile.record(pc, next_pc));
+    (outcome, profile)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Breakpoint {
+    Pc(usize),
+    Kind(Kind)
+}
+
+impl Breakpoint {
+    fn hit(&self, pc: usize, instruction: &Instruction) -> bool {
+        match self {
+            Breakpoint::Pc(at) => *at == pc,
+            Breakpoint::Kind(kind) => *kind == instruction.kind()
+        }
+    }
+}
+
+impl FromStr for Breakpoint {
+    type Err = InstructionParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<usize>() {
+            Ok(pc) => Ok(Breakpoint::Pc(pc)),
+            Err(_) => s.parse::<Kind>().map(Breakpoint::Kind)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Watchpoint<W: Word> {
+    Change(Register),
+    Equals(Register, W)
+}
+
+impl<W: Word> Watchpoint<W> {
+    fn hit(&self, before: &[W], after: &[W]) -> bool {
+        match self {
+            Watchpoint::Change(r) => before[r_to_i(r)] != after[r_to_i(r)],
+            Watchpoint::Equals(r, v) => before[r_to_i(r)] != *v && after[r_to_i(r)] == *v
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Event {
+    Stepped,
+    Stopped(RunOutcome),
+    Breakpoint(usize),
+    Watchpoint(usize)
+}
+
+#[derive(Clone, Debug)]
+struct TraceEntry<W: Word> {
+    pc: usize,
+    registers: Vec<W>
+}
+
+struct Debugger<'a, W: Word> {
+    computer: Computer<W>,
+    program: &'a Program,
+    breakpoints: Vec<Breakpoint>,
+    watchpoints: Vec<Watchpoint<W>>,
+    watchdog: Watchdog<W>,
+    trace: Option<Vec<TraceEntry<W>>>,
+    // Where the last step or continue left off
+    stopped_at: Option<usize>
+}
+
+impl<'a, W: Word> Debugger<'a, W> {
+    fn new(computer: Computer<W>, program: &'a Program) -> Debugger<'a, W> {
+        Debugger {
+            computer, program, breakpoints: vec![], watchpoints: vec![], watchdog: Watchdog::new(&Limits::default()), trace: None,
+            stopped_at: None
+        }
+    }
+
+    fn set_limits(&mut self, limits: &Limits) {
+        self.watchdog = Watchdog::new(limits);
+    }
+
+    fn enable_trace(&mut self) {
+        self.trace = Some(vec![]);
+    }
+
+    fn step(&mut self) -> Event {
+        let event = self.execute();
+        self.stopped_at = Some(self.computer.pc);
+        event
+    }
+
+    fn execute(&mut self) -> Event {
+        let pc = self.computer.pc;
+        let before = self.computer.registers.clone();
+
+        if self.computer.halted(self.program) {
+            return Event::Stopped(RunOutcome::Halted);
+        }
+        if let Err(outcome) = self.watchdog.check(&self.computer) {
+            return Event::Stopped(outcome);
+        }
+        if let Err(outcome) = self.computer.step(self.program) {
+            return Event::Stopped(outcome);
+        }
+
+        if let Some(trace) = self.trace.as_mut() {
+            trace.push(TraceEntry { pc, registers: self.computer.registers.clone() });
+        }
+
+        let after = &self.computer.registers;
+        if let Some(i) = self.watchpoints.iter().position(|w| w.hit(&before, after)) {
+            Event::Watchpoint(i)
+        } else if self.computer.halted(self.program) {
+            Event::Stopped(RunOutcome::Halted)
+        } else {
+            Event::Stepped
+        }
+    }
+
+    // Run until stopped by the program, a break- or watchpoint. A breakpoint on the current
+    // instruction only counts when it hasn't stopped there yet, so that continuing from a
+    // breakpoint or a step moves on.
+    fn cont(&mut self) -> Event {
+        let pc = self.computer.pc;
+        if self.stopped_at != Some(pc) && pc < self.program.instructions.len() {
+            self.stopped_at = Some(pc);
+            if let Some(i) = self.breakpoints.iter().position(|b| b.hit(pc, &self.program.instructions[pc])) {
+                return Event::Breakpoint(i);
+            }
+        }
+
+        loop {
+            match self.step() {
+                Event::Stepped => {},
+                event => return event
+            }
+
+            let pc = self.computer.pc;
+            let instruction = &self.program.instructions[pc];
+            if let Some(i) = self.breakpoints.iter().position(|b| b.hit(pc, instruction)) {
+                return Event::Breakpoint(i);
+            }
+        }
+    }
+
+    fn write_trace<O: Write>(&self, writer: &mut O) -> io::Result<()> {
+        if let Some(trace) = self.trace.as_ref() {
+            for entry in trace {
+                writeln!(writer, "{}", self.program.format_state(entry.pc, &entry.registers))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn debug<W: Word, R: BufRead>(program: &Program, overflow: Overflow, limits: &Limits, commands: R) {
+    let mut debugger = Debugger::<W>::new(Computer::new(program, overflow), program);
+    debugger.set_limits(limits);
+
+    let print_state = |debugger: &Debugger<W>| {
+        println!("{}", program.format_state(debugger.computer.pc, &debugger.computer.registers));
+    };
+
+    print_state(&debugger);
+    for line in commands.lines() {
+        let line = line.unwrap();
+        let args: Vec<_> = line.split_whitespace().collect();
+        if args.is_empty() {
+            continue;
+        }
+
+        match (args[0], &args[1..]) {
+            ("s", rest) | ("step", rest) => {
+                let n = rest.first().and_then(|n| n.parse().ok()).unwrap_or(1);
+                for _ in 0..n {
+                    match debugger.step() {
+                        Event::Stepped => {},
+                        event => {
+                            println!("{:?}", event);
+                            break;
+                        }
                     }
                 }
+            },
+            ("c", _) | ("continue", _) => {
+                println!("{:?}", debugger.cont());
+            },
+            ("b", [at]) | ("break", [at]) => {
+                match at.parse::<Breakpoint>() {
+                    Ok(b) => debugger.breakpoints.push(b),
+                    Err(_) => println!("Invalid breakpoint: {}", at)
+                }
+            },
+            ("w", [r]) | ("watch", [r]) => {
+                match program.register(r) {
+                    Some(r) => debugger.watchpoints.push(Watchpoint::Change(r)),
+                    None => println!("Invalid register: {}", r)
+                }
+            },
+            ("w", [r, v]) | ("watch", [r, v]) => {
+                match (program.register(r), v.parse::<W>()) {
+                    (Some(r), Ok(v)) => debugger.watchpoints.push(Watchpoint::Equals(r, v)),
+                    _ => println!("Invalid watchpoint: {} {}", r, v)
+                }
+            },
+            ("set", [r, v]) => {
+                match (program.register(r), v.parse::<W>()) {
+                    (Some(r), Ok(v)) => debugger.computer.set_register(r, v),
+                    _ => println!("Invalid register value: {} {}", r, v)
+                }
+            },
+            ("l", _) | ("list", _) => {
+                for (i, b) in debugger.breakpoints.iter().enumerate() {
+                    println!("breakpoint {}: {:?}", i, b);
+                }
+                for (i, w) in debugger.watchpoints.iter().enumerate() {
+                    println!("watchpoint {}: {:?}", i, w);
+                }
+            },
+            ("r", _) | ("regs", _) => {},
+            ("q", _) | ("quit", _) => break,
+            _ => {
+                println!("Commands: s|step [n], c|continue, b|break <pc|kind>, w|watch <reg> [value], set <reg> <value>, l|list, r|regs, q|quit");
+                continue;
             }
         }
+        print_state(&debugger);
     }
+}
 
-    fn set_register(&mut self, register: Register, value: i32) {
-        self.registers[r_to_i(&register)] = value;
+fn trace<'a, W: Word>(program: &'a Program, computer: Computer<W>, limits: &Limits) -> Debugger<'a, W> {
+    let mut debugger = Debugger::new(computer, program);
+    debugger.set_limits(limits);
+    debugger.enable_trace();
+    debugger.cont();
+    debugger
+}
+
+fn part2_computer<W: Word>(input: &Input, overflow: Overflow) -> Computer<W> {
+    let mut computer = Computer::new(input, overflow);
+    if let Some(a) = input.register("a") {
+        computer.set_register(a, W::one());
     }
+    computer
+}
 
-    fn peek_register(&self, register: Register) -> i32 {
-        self.registers[r_to_i(&register)]
+fn run_for_b<W: Word + 'static>(mut computer: Computer<W>, input: &Input, limits: &Limits, interpreter: Interpreter) -> Result<W, RunOutcome> {
+    let outcome = match interpreter {
+        Interpreter::Reference => computer.run(input, limits),
+        Interpreter::Compiled => CompiledProgram::compile(input, computer.overflow).run(&mut computer, limits)
+    };
+    if outcome.terminated() {
+        Ok(input.register("b").map_or_else(W::zero, |b| computer.peek_register(b)))
+    } else {
+        Err(outcome)
     }
 }
 
-fn part1(input: &Input) -> i32 {
-    let mut computer = Computer::new();
-    computer.run(input);
-    computer.peek_register(Register::B)
+fn part1<W: Word + 'static>(input: &Input, overflow: Overflow, limits: &Limits, interpreter: Interpreter) -> Result<W, RunOutcome> {
+    run_for_b(Computer::new(input, overflow), input, limits, interpreter)
+}
+
+fn part2<W: Word + 'static>(input: &Input, overflow: Overflow, limits: &Limits, interpreter: Interpreter) -> Result<W, RunOutcome> {
+    run_for_b(part2_computer(input, overflow), input, limits, interpreter)
+}
+
+fn print_result<W: Word>(part: &str, result: Result<W, RunOutcome>) {
+    match result {
+        Ok(b) => println!("{}: {}", part, b),
+        Err(outcome) => println!("{}: did not terminate, {:?}", part, outcome)
+    }
 }
 
-fn part2(input: &Input) -> i32 {
-    let mut computer = Computer::new();
-    computer.set_register(Register::A, 1);
-    computer.run(input);
-    computer.peek_register(Register::B)
+struct Options {
+    input: String,
+    debug: bool,
+    disassemble: bool,
+    profile: bool,
+    trace: Option<String>,
+    bench: Option<usize>,
+    interpreter: Interpreter,
+    limits: Limits,
+    width: Width,
+    overflow: Overflow
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Options {
+        let mut input = None;
+        let mut debug = false;
+        let mut disassemble = false;
+        let mut profile = false;
+        let mut trace = None;
+        let mut bench = None;
+        let mut interpreter = Interpreter::Compiled;
+        let mut limits = Limits::default();
+        let mut width = Width::U64;
+        let mut overflow = Overflow::Trap;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--debug" => debug = true,
+                "--disassemble" => disassemble = true,
+                "--profile" => profile = true,
+                "--trace" => trace = Some(args.next().expect("No trace file given")),
+                "--bench" => bench = Some(args.next().and_then(|n| n.parse().ok()).expect("No valid number of runs given")),
+                "--reference" => interpreter = Interpreter::Reference,
+                "--budget" => limits.budget = Some(args.next().and_then(|b| b.parse().ok()).expect("No valid budget given")),
+                "--detect-loops" => limits.detect_loops = true,
+                "--width" => width = args.next().expect("No width given").parse().unwrap(),
+                "--overflow" => overflow = args.next().expect("No overflow behaviour given").parse().unwrap(),
+                _ => input = Some(arg)
+            }
+        }
+
+        Options { input: input.expect("No input file given"), debug, disassemble, profile, trace, bench, interpreter, limits, width, overflow }
+    }
 }
 
 fn main() {
+    let options = Options::parse(env::args().skip(1));
+
+    if options.disassemble {
+        let input = input(&options.input).expect("Input failed");
+        print!("{}", input.disassemble());
+        return;
+    }
+
+    match options.width {
+        Width::U8 => solve::<u8>(&options),
+        Width::U16 => solve::<u16>(&options),
+        Width::U32 => solve::<u32>(&options),
+        Width::U64 => solve::<u64>(&options),
+        Width::U128 => solve::<u128>(&options),
+        Width::Big => solve::<BigUint>(&options)
+    }
+}
+
+fn solve<W: Word + 'static>(options: &Options) {
+    if options.debug {
+        let input = input(&options.input).expect("Input failed");
+        let stdin = io::stdin();
+        debug::<W, _>(&input, options.overflow, &options.limits, stdin.lock());
+        return;
+    }
+
+    if options.profile {
+        let input = input(&options.input).expect("Input failed");
+        for (part, computer) in [("Part1", Computer::<W>::new(&input, options.overflow)), ("Part2", part2_computer(&input, options.overflow))] {
+            let (outcome, profile) = profile(computer, &input, &options.limits);
+            println!("{}: {:?}", part, outcome);
+            print!("{}", profile.report(&input));
+        }
+        return;
+    }
+
+    if let Some(times) = options.bench {
+        let input = input(&options.input).expect("Input failed");
+        for interpreter in [Interpreter::Reference, Interpreter::Compiled] {
+            println!("{:?}:", interpreter);
+            measure_times(times, || {
+                part1::<W>(&input, options.overflow, &options.limits, interpreter).ok();
+                part2::<W>(&input, options.overflow, &options.limits, interpreter).ok();
+            });
+        }
+        return;
+    }
+
     measure(|| {
-        let input = input().expect("Input failed");
-        println!("Part1: {}", part1(&input));
-        println!("Part2: {}", part2(&input));
+        let input = input(&options.input).expect("Input failed");
+        print_result("Part1", part1::<W>(&input, options.overflow, &options.limits, options.interpreter));
+        print_result("Part2", part2::<W>(&input, options.overflow, &options.limits, options.interpreter));
     });
+
+    if let Some(path) = options.trace.as_ref() {
+        let input = input(&options.input).expect("Input failed");
+        let mut f = File::create(path).expect("Unable to create trace file");
+        writeln!(f, "# Part1").unwrap();
+        trace::<W>(&input, Computer::new(&input, options.overflow), &options.limits).write_trace(&mut f).expect("Unable to write trace");
+        writeln!(f, "# Part2").unwrap();
+        trace::<W>(&input, part2_computer(&input, options.overflow), &options.limits).write_trace(&mut f).expect("Unable to write trace");
+    }
 }
 
-fn input() -> io::Result<Input> {
-    let f = File::open(env::args().skip(1).next().expect("No input file given"))?;
+fn input(path: &str) -> io::Result<Input> {
+    let f = File::open(path)?;
     let f = BufReader::new(f);
-    Ok(Input::read(f))
+    Input::read(f).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
 }
 
 
@@ -192,13 +1252,315 @@ mod tests {
 
     fn as_input(s: &str) -> Input {
         let f = BufReader::new(s.as_bytes());
-        Input::read(f)
+        Input::read(f).unwrap()
+    }
+
+    fn computer(program: &Program) -> Computer<u64> {
+        Computer::new(program, Overflow::Trap)
+    }
+
+    fn register(program: &Program, name: &str) -> Register {
+        program.register(name).unwrap()
     }
 
     #[test]
     fn test_computer() {
-        let mut computer = Computer::new();
-        computer.run(&as_input(INPUT));
-        assert_eq!(computer.peek_register(Register::A), 2);
+        let program = as_input(INPUT);
+        let mut computer = computer(&program);
+        assert_eq!(computer.run(&program, &Limits::default()), RunOutcome::Halted);
+        assert_eq!(computer.peek_register(register(&program, "a")), 2);
+    }
+
+    #[test]
+    fn test_jumped_out_of_bounds() {
+        let program = as_input("inc a\njmp -2");
+        let mut out_of_bounds = computer(&program);
+        assert_eq!(out_of_bounds.run(&program, &Limits::default()), RunOutcome::JumpedOutOfBounds { pc: 1, target: -1 });
+        assert_eq!(out_of_bounds.pc, 1);
+
+        let program = as_input("jmp +3\ninc a");
+        assert_eq!(computer(&program).run(&program, &Limits::default()), RunOutcome::JumpedOutOfBounds { pc: 0, target: 3 });
+
+        let program = as_input("jmp +2\ninc a");
+        assert_eq!(computer(&program).run(&program, &Limits::default()), RunOutcome::Halted);
+
+        // Started past the end, where the compiled program keeps its exits
+        let program = as_input("inc a\njmp -2\ntpl a");
+        for pc in [3, 4, 5, 10] {
+            for limits in [Limits::default(), Limits { budget: Some(10), detect_loops: true }] {
+                let mut reference = computer(&program);
+                reference.pc = pc;
+                let mut compiled = reference.clone();
+                assert_eq!(reference.run(&program, &limits), RunOutcome::Halted);
+                assert_eq!(CompiledProgram::compile(&program, Overflow::Trap).run(&mut compiled, &limits), RunOutcome::Halted);
+                assert_eq!((reference.pc, compiled.pc), (pc, pc));
+            }
+        }
+        assert_eq!(part1::<u64>(&as_input("inc b\njmp -2"), Overflow::Trap, &Limits::default(), Interpreter::Reference), Ok(1));
+    }
+
+    #[test]
+    fn test_limits() {
+        let program = as_input("inc b\njmp +1\njmp -1");
+        let budget = Limits { budget: Some(10), detect_loops: false };
+        let detect = Limits { budget: None, detect_loops: true };
+
+        assert_eq!(computer(&program).run(&program, &budget), RunOutcome::BudgetExceeded { steps: 10 });
+        assert_eq!(computer(&program).run(&program, &detect), RunOutcome::LoopDetected { pc: 1, steps: 5 });
+        assert_eq!(part1::<u64>(&program, Overflow::Trap, &detect, Interpreter::Reference), Err(RunOutcome::LoopDetected { pc: 1, steps: 5 }));
+
+        // Revisits pc 0 once, with a different value in a, before halting after 9 steps
+        let growing = as_input("inc a\ntpl a\njio a, +3\njie a, +2\njmp -4");
+        assert_eq!(computer(&growing).run(&growing, &detect), RunOutcome::Halted);
+        assert_eq!(computer(&growing).run(&growing, &Limits { budget: Some(9), detect_loops: true }), RunOutcome::Halted);
+        assert_eq!(computer(&growing).run(&growing, &Limits { budget: Some(8), detect_loops: true }), RunOutcome::BudgetExceeded { steps: 8 });
+    }
+
+    #[test]
+    fn test_named_registers() {
+        let program = as_input(
+           "inc counter
+            inc counter
+            loop:
+                tpl acc_1
+                inc acc_1
+                hlf counter
+                jio counter, loop
+            inc b");
+        assert_eq!(program.registers, vec!["counter", "acc_1", "b"]);
+        assert_eq!(program.instructions[2], Instruction::Tpl(Register(1)));
+
+        let mut computer = computer(&program);
+        computer.run(&program, &Limits::default());
+        assert_eq!(computer.peek_register(register(&program, "acc_1")), 4);
+        assert_eq!(part1::<u64>(&program, Overflow::Trap, &Limits::default(), Interpreter::Reference), Ok(1));
+        assert_eq!(part2::<u64>(&program, Overflow::Trap, &Limits::default(), Interpreter::Reference), Ok(1));
+    }
+
+    #[test]
+    fn test_overflow() {
+        // 3^6 = 729 does not fit in 8 bits
+        let program = as_input("inc b\ntpl b\ntpl b\ntpl b\ntpl b\ntpl b\ntpl b");
+        let run = |overflow| part1::<u8>(&program, overflow, &Limits::default(), Interpreter::Reference);
+        assert_eq!(run(Overflow::Wrap), Ok((729 % 256) as u8));
+        assert_eq!(run(Overflow::Saturate), Ok(255));
+        assert_eq!(run(Overflow::Trap), Err(RunOutcome::Overflow { pc: 6 }));
+        assert_eq!(part1::<u16>(&program, Overflow::Trap, &Limits::default(), Interpreter::Reference), Ok(729));
+
+        let program = as_input("tpl b\ninc b\njmp -2");
+        let budget = Limits { budget: Some(1000), detect_loops: false };
+        assert_eq!(part1::<u32>(&program, Overflow::Trap, &budget, Interpreter::Reference), Err(RunOutcome::Overflow { pc: 0 }));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_big_registers() {
+        let program = as_input(&format!("inc b\n{}hlf b\ninc b", "tpl b\n".repeat(50)));
+        assert_eq!(part1::<u64>(&program, Overflow::Trap, &Limits::default(), Interpreter::Reference), Err(RunOutcome::Overflow { pc: 41 }));
+        let b = part1::<BigUint>(&program, Overflow::Trap, &Limits::default(), Interpreter::Reference).unwrap();
+        assert_eq!(b.to_string(), "358948993845926294385125");
+
+        let n = "717897987691852588770249".parse::<BigUint>().unwrap();
+        assert_eq!(n.to_string(), "717897987691852588770249");
+        assert!(!n.is_even() && !n.is_one());
+        assert_eq!(BigUint::zero().to_string(), "0");
+        assert_eq!("1".parse::<BigUint>(), Ok(BigUint::one()));
+        assert!("12a".parse::<BigUint>().is_err());
+    }
+
+    fn random_program(rng: &mut Lcg, len: usize) -> String {
+        (0..len)
+            .map(|_| {
+                let r = ["a", "b", "c"][rng.between(0, 2) as usize];
+                let o = rng.between(-4, 4);
+                match rng.between(0, 5) {
+                    0 => format!("hlf {}", r),
+                    1 => format!("tpl {}", r),
+                    2 => format!("inc {}", r),
+                    3 => format!("jmp {:+}", o),
+                    4 => format!("jie {}, {:+}", r, o),
+                    _ => format!("jio {}, {:+}", r, o)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn assert_same_run<W: Word + 'static>(program: &Program, overflow: Overflow, limits: &Limits) {
+        let mut reference = Computer::<W>::new(program, overflow);
+        let mut compiled = reference.clone();
+        let expected = reference.run(program, limits);
+        let outcome = CompiledProgram::compile(program, overflow).run(&mut compiled, limits);
+        assert_eq!(outcome, expected, "{}", program.disassemble());
+        assert_eq!(compiled.pc, reference.pc);
+        assert_eq!(compiled.registers, reference.registers);
+
+        // Runs that finish within the limits also have to finish the same way through the blocks
+        if !matches!(expected, RunOutcome::BudgetExceeded { .. } | RunOutcome::LoopDetected { .. }) {
+            let mut unlimited = Computer::<W>::new(program, overflow);
+            assert_eq!(CompiledProgram::compile(program, overflow).run(&mut unlimited, &Limits::default()), expected);
+            assert_eq!(unlimited.pc, reference.pc);
+            assert_eq!(unlimited.registers, reference.registers);
+        }
+    }
+
+    #[test]
+    fn test_compiled_matches_reference() {
+        let mut rng = Lcg::new(0x2015_1223);
+        for i in 0..2000 {
+            let program = as_input(&format!("inc a\n{}", random_program(&mut rng, 2 + i % 20)));
+            let budget = Limits { budget: Some(500), detect_loops: false };
+            let detect = Limits { budget: Some(500), detect_loops: true };
+            assert_same_run::<u64>(&program, Overflow::Trap, &budget);
+            assert_same_run::<u64>(&program, Overflow::Trap, &detect);
+            assert_same_run::<u8>(&program, Overflow::Wrap, &budget);
+            assert_same_run::<u8>(&program, Overflow::Saturate, &detect);
+            assert_same_run::<u8>(&program, Overflow::Trap, &budget);
+            assert_same_run::<BigUint>(&program, Overflow::Trap, &budget);
+        }
+
+        let program = as_input(INPUT);
+        assert_same_run::<u64>(&program, Overflow::Trap, &Limits::default());
+        let program = as_input("inc a\njmp -2");
+        assert_same_run::<u64>(&program, Overflow::Trap, &Limits::default());
+        let program = as_input(&format!("inc b\n{}", "tpl b\n".repeat(50)));
+        assert_same_run::<u64>(&program, Overflow::Trap, &Limits::default());
+        assert_eq!(part1::<u64>(&program, Overflow::Trap, &Limits::default(), Interpreter::Compiled), Err(RunOutcome::Overflow { pc: 41 }));
+    }
+
+    #[test]
+    fn test_profile() {
+        let program = as_input(
+           "inc a
+            tpl a
+            outer:
+                inc b
+                tpl b
+                inc b
+            inner:
+                hlf b
+                jie b, inner
+                hlf a
+                jio a, outer");
+        let (outcome, profile) = profile(computer(&program), &program, &Limits::default());
+        assert_eq!(outcome, RunOutcome::Halted);
+        assert_eq!(profile.steps, 18);
+        assert_eq!(profile.pc_counts, vec![1, 1, 2, 2, 2, 3, 3, 2, 2]);
+        assert_eq!(profile.kind_counts(&program), vec![(Kind::Inc, 5), (Kind::Hlf, 5), (Kind::Tpl, 3), (Kind::Jie, 3), (Kind::Jio, 2)]);
+        assert_eq!(profile.hot_loops(), vec![
+            HotLoop { start: 2, end: 8, iterations: 1, steps: 16 },
+            HotLoop { start: 5, end: 6, iterations: 1, steps: 6 }
+        ]);
+        assert!(profile.report(&program).starts_with("Executed 18 instructions\nBy instruction kind:\n"));
+    }
+
+    #[test]
+    fn test_debugger_breakpoints() {
+        let program = as_input(INPUT);
+        let mut debugger = Debugger::new(computer(&program), &program);
+        debugger.breakpoints.push(Breakpoint::Pc(3));
+        debugger.breakpoints.push(Breakpoint::Kind(Kind::Jio));
+
+        assert_eq!(debugger.cont(), Event::Breakpoint(1));
+        assert_eq!(debugger.computer.pc, 1);
+        assert_eq!(debugger.step(), Event::Stepped);
+        assert_eq!(debugger.computer.pc, 3);
+        assert_eq!(debugger.cont(), Event::Stopped(RunOutcome::Halted));
+        assert_eq!(debugger.computer.peek_register(register(&program, "a")), 2);
+
+        // A breakpoint on the first instruction stops before it runs, once
+        let mut debugger = Debugger::new(computer(&program), &program);
+        debugger.breakpoints.push(Breakpoint::Pc(0));
+        assert_eq!(debugger.cont(), Event::Breakpoint(0));
+        assert_eq!(debugger.computer.pc, 0);
+        assert_eq!(debugger.computer.peek_register(register(&program, "a")), 0);
+        assert_eq!(debugger.cont(), Event::Stopped(RunOutcome::Halted));
+
+        let program = as_input("jmp +1\njmp -1");
+        let mut debugger = Debugger::new(computer(&program), &program);
+        debugger.set_limits(&Limits { budget: None, detect_loops: true });
+        assert_eq!(debugger.cont(), Event::Stopped(RunOutcome::LoopDetected { pc: 0, steps: 4 }));
+    }
+
+    #[test]
+    fn test_debugger_watchpoints() {
+        let program = as_input(&format!("{}\ninc b", INPUT));
+        let mut debugger = Debugger::new(computer(&program), &program);
+        debugger.watchpoints.push(Watchpoint::Equals(register(&program, "a"), 2));
+        debugger.watchpoints.push(Watchpoint::Change(register(&program, "b")));
+
+        assert_eq!(debugger.cont(), Event::Watchpoint(0));
+        assert_eq!(debugger.computer.pc, 4);
+        assert_eq!(debugger.step(), Event::Watchpoint(1));
+        assert_eq!(debugger.step(), Event::Stopped(RunOutcome::Halted));
+    }
+
+    #[test]
+    fn test_trace() {
+        let program = as_input(INPUT);
+        let mut trace_output = vec![];
+        trace(&program, computer(&program), &Limits::default()).write_trace(&mut trace_output).unwrap();
+        assert_eq!(String::from_utf8(trace_output).unwrap(),
+            "   0  inc a        a=1\n   1  jio a, +2    a=1\n   3  inc a        a=2\n");
+    }
+
+    #[test]
+    fn test_assemble_labels() {
+        let program = Program::assemble(
+           "; count b up to 3 using a as the loop condition
+            start:  inc b
+                    jio a, done  # a is only 1 after the third round
+            loop:
+                    inc a
+                    jmp start
+            done:").unwrap();
+        assert_eq!(program.instructions, vec![
+            Instruction::Inc(Register(0)),
+            Instruction::Jio(Register(1), 3),
+            Instruction::Inc(Register(1)),
+            Instruction::Jmp(-3)
+        ]);
+
+        let mut computer = computer(&program);
+        computer.run(&program, &Limits::default());
+        assert_eq!(computer.peek_register(register(&program, "b")), 2);
+    }
+
+    #[test]
+    fn test_assemble_errors() {
+        let error = |source: &str| Program::assemble(source).unwrap_err();
+        assert_eq!(error("inc a\nmul a"), AssembleError { line: 2, kind: AssembleErrorKind::UnknownInstruction("mul".into()) });
+        assert_eq!(error("in"), AssembleError { line: 1, kind: AssembleErrorKind::UnknownInstruction("in".into()) });
+        assert_eq!(error("inc 1c"), AssembleError { line: 1, kind: AssembleErrorKind::InvalidRegister("1c".into()) });
+        assert_eq!(error("jie a"), AssembleError { line: 1, kind: AssembleErrorKind::WrongOperandCount(Kind::Jie, 1) });
+        assert_eq!(error("jmp"), AssembleError { line: 1, kind: AssembleErrorKind::WrongOperandCount(Kind::Jmp, 0) });
+        assert_eq!(error("jmp +x"), AssembleError { line: 1, kind: AssembleErrorKind::InvalidOffset("+x".into()) });
+        assert_eq!(error("jmp end"), AssembleError { line: 1, kind: AssembleErrorKind::UndefinedLabel("end".into()) });
+        assert_eq!(error("a:\na:"), AssembleError { line: 2, kind: AssembleErrorKind::DuplicateLabel("a".into()) });
+        assert_eq!(error("1a: inc a"), AssembleError { line: 1, kind: AssembleErrorKind::InvalidLabel("1a".into()) });
+    }
+
+    #[test]
+    fn test_disassemble_round_trip() {
+        let program = as_input(
+           "jio a, +5
+            inc a
+            tpl a
+            jmp +2
+            jmp -4
+            jio a, +8
+            inc b
+            jie a, +4
+            tpl a
+            inc a
+            jmp +2
+            hlf a
+            jmp -7");
+        let text = program.disassemble();
+        let reassembled = Program::assemble(&text).unwrap();
+        assert_eq!(reassembled, program);
+        assert_eq!(reassembled.disassemble(), text);
+
+        assert_eq!(as_input(INPUT).disassemble(), "    inc a\n    jio a, l0\n    tpl a\nl0:\n    inc a\n");
+        assert_eq!(as_input("jmp -2\njie a, +3\njie a, +1").disassemble(), "    jmp -2\n    jie a, +3\n    jie a, l0\nl0:\n");
+    }
+}
@@ -1,7 +1,11 @@
 extern crate utils;
 extern crate rayon;
 
+use std::cmp;
+use std::collections::{HashMap, HashSet};
 use std::env;
+use std::error;
+use std::fmt;
 use std::io::{self, BufReader};
 use std::io::prelude::*;
 use std::fs::File;
@@ -63,47 +67,409 @@ impl Iterator for Combinations {
     }
 }
 
-fn quantum_entanglement(packages: Input) -> u64 {
-    packages.iter().map(|p| *p as u64).product::<u64>()
+// Exact however many packages there are, a few dozen are already beyond any fixed width
+fn quantum_entanglement(packages: &[u32]) -> BigUint {
+    let mut qe = BigUint::from(1);
+    for p in packages {
+        qe.mul_add_small(*p, 0);
+    }
+    qe
+}
+
+#[derive(Debug, PartialEq)]
+enum BalanceError {
+    NoGroups,
+    TooFewPackages { packages: usize, groups: u32 },
+    TooHeavy,
+    UnevenTotal { total: u32, groups: u32 },
+    Infeasible { groups: u32, per_group: u32 }
+}
+
+impl fmt::Display for BalanceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BalanceError::NoGroups => write!(f, "there has to be at least one group"),
+            BalanceError::TooFewPackages { packages, groups } =>
+                write!(f, "{} packages can't fill {} groups", packages, groups),
+            BalanceError::TooHeavy => write!(f, "the total weight of the packages is too large"),
+            BalanceError::UnevenTotal { total, groups } =>
+                write!(f, "the total weight {} can't be divided evenly into {} groups", total, groups),
+            BalanceError::Infeasible { groups, per_group } =>
+                write!(f, "the packages can't be split into {} groups weighing {} each", groups, per_group)
+        }
+    }
+}
+
+impl error::Error for BalanceError {}
+
+// The packages split into groups of equal weight given as indexes into the package list, the
+// first group is the one in the passenger compartment
+#[derive(Debug, PartialEq)]
+struct Balance {
+    groups: Vec<Vec<usize>>,
+    quantum_entanglement: BigUint
+}
+
+// The weight every group has to get
+fn per_group(packages: &Input, n_groups: u32) -> Result<u32, BalanceError> {
+    if n_groups == 0 {
+        return Err(BalanceError::NoGroups);
+    }
+    if packages.len() < n_groups as usize {
+        return Err(BalanceError::TooFewPackages { packages: packages.len(), groups: n_groups });
+    }
+    let total = packages.iter().try_fold(0u32, |total, p| total.checked_add(*p)).ok_or(BalanceError::TooHeavy)?;
+    if total % n_groups != 0 {
+        return Err(BalanceError::UnevenTotal { total, groups: n_groups });
+    }
+    Ok(total / n_groups)
+}
+
+// The first group has as few packages as possible and among those the lowest quantum
+// entanglement, but only counts when the rest of the packages can be split evenly as well.
+//
+// With the packages sorted by weight a table of the fewest packages from each suffix that add
+// up to each weight gives the size of the first group and prunes every branch of the search
+// that can't reach the remaining weight with the packages it has left. At that size the table
+// also fixes how many packages every branch still needs, so there are few enough branches to
+// remember the lowest quantum entanglement each of them can still reach. The search follows the
+// lower of the two first and stops a branch that can't beat the best group found so far.
+fn ideal_quantum_entanglement(packages: &Input, n_groups: u32) -> Result<Balance, BalanceError> {
+    let per_group = per_group(packages, n_groups)?;
+    let target = per_group as usize;
+    let mut order: Vec<usize> = (0..packages.len()).collect();
+    order.sort_by_key(|i| (packages[*i], *i));
+    let weights: Vec<u32> = order.iter().map(|i| packages[*i]).collect();
+    let n = weights.len();
+
+    let unreachable = u16::MAX;
+    let mut fewest = vec![unreachable; (n + 1) * (target + 1)];
+    fewest[n * (target + 1)] = 0;
+    for j in (0..n).rev() {
+        let w = weights[j] as usize;
+        for sum in 0..=target {
+            let without = fewest[(j + 1) * (target + 1) + sum];
+            let with = if w <= sum { fewest[(j + 1) * (target + 1) + sum - w].saturating_add(1) } else { unreachable };
+            fewest[j * (target + 1) + sum] = cmp::min(without, with);
+        }
+    }
+
+    let smallest = fewest[target];
+    if smallest == unreachable {
+        return Err(BalanceError::Infeasible { groups: n_groups, per_group });
+    }
+
+    let mut search = FirstGroupSearch { packages, order: &order, weights: &weights, fewest: &fewest, target, n_groups, least: HashMap::new() };
+    let max_packages = n - (n_groups as usize - 1);
+    for size in smallest as usize..=max_packages {
+        let mut best = None;
+        if search.least(0, size, target).is_some() {
+            search.find(0, size, target, &BigUint::from(1), &mut vec![], &mut best);
+        }
+        if let Some(balance) = best {
+            return Ok(balance);
+        }
+    }
+
+    Err(BalanceError::Infeasible { groups: n_groups, per_group })
+}
+
+struct FirstGroupSearch<'a> {
+    packages: &'a Input,
+    order: &'a [usize],
+    weights: &'a [u32],
+    fewest: &'a [u16],
+    target: usize,
+    n_groups: u32,
+    least: HashMap<(usize, usize, usize), Option<BigUint>>
 }
 
-fn ideal_quantum_entanglement(packages: &Input, n_groups: u32) -> u64 {
-    let n_packages = packages.len();
-    let per_group = packages.iter().sum::<u32>() / n_groups;
-    let max_packages = n_packages - (n_groups as usize - 1);
+impl<'a> FirstGroupSearch<'a> {
+    // Picks left more packages from position j on in weight order adding up to remaining, which
+    // least has to say is possible
+    fn find(&mut self, j: usize, left: usize, remaining: usize, qe: &BigUint, chosen: &mut Vec<usize>, best: &mut Option<Balance>) {
+        if left == 0 {
+            if best.as_ref().is_none_or(|b| *qe < b.quantum_entanglement) {
+                if let Some(balance) = self.complete(chosen, qe) {
+                    *best = Some(balance);
+                }
+            }
+            return;
+        }
+
+        let w = self.weights[j];
+        let next = self.next_weight(j);
+        let mut branches = vec![];
+        if w as usize <= remaining {
+            if let Some(mut least) = self.least(j + 1, left - 1, remaining - w as usize) {
+                least.mul_add_small(w, 0);
+                branches.push((least, true));
+            }
+        }
+        if let Some(least) = self.least(next, left, remaining) {
+            branches.push((least, false));
+        }
+        branches.sort();
+
+        for (least, with) in branches {
+            if best.as_ref().is_some_and(|b| &least * qe >= b.quantum_entanglement) {
+                return;
+            }
+            if with {
+                let mut qe = qe.clone();
+                qe.mul_add_small(w, 0);
+                chosen.push(j);
+                self.find(j + 1, left - 1, remaining - w as usize, &qe, chosen, best);
+                chosen.pop();
+            } else {
+                self.find(next, left, remaining, qe, chosen, best);
+            }
+        }
+    }
 
-    (1..=max_packages)
-        .map(|n| {
-            Combinations::new(packages, n)
-                .filter(|packages| packages.iter().sum::<u32>() == per_group)
-                .map(quantum_entanglement)
-                .min()
+    // The lowest quantum entanglement of left packages from position j on adding up to
+    // remaining, if there are any
+    fn least(&mut self, j: usize, left: usize, remaining: usize) -> Option<BigUint> {
+        if left == 0 {
+            return if remaining == 0 { Some(BigUint::from(1)) } else { None };
+        }
+        if j + left > self.weights.len() || self.weights[j] as usize > remaining || self.fewest[j * (self.target + 1) + remaining] as usize > left {
+            return None;
+        }
+        if let Some(least) = self.least.get(&(j, left, remaining)) {
+            return least.clone();
+        }
+
+        let w = self.weights[j];
+        let with = self.least(j + 1, left - 1, remaining - w as usize).map(|mut least| {
+            least.mul_add_small(w, 0);
+            least
+        });
+        let next = self.next_weight(j);
+        let without = self.least(next, left, remaining);
+        let least = match (with, without) {
+            (Some(with), Some(without)) => Some(cmp::min(with, without)),
+            (with, without) => with.or(without)
+        };
+        self.least.insert((j, left, remaining), least.clone());
+        least
+    }
+
+    // Leaving out a package and taking one just as heavy after it gives the same groups again
+    fn next_weight(&self, j: usize) -> usize {
+        let mut next = j + 1;
+        while next < self.weights.len() && self.weights[next] == self.weights[j] {
+            next += 1;
+        }
+        next
+    }
+
+    fn complete(&self, chosen: &[usize], qe: &BigUint) -> Option<Balance> {
+        let first: Vec<usize> = chosen.iter().map(|j| self.order[*j]).collect();
+        let rest: Vec<usize> = (0..self.packages.len()).filter(|i| !first.contains(i)).collect();
+        split(self.packages, &rest, self.n_groups as usize - 1, self.target as u32).map(|others| {
+            let mut groups = vec![first];
+            groups.extend(others);
+            Balance { groups, quantum_entanglement: qe.clone() }
         })
-        .skip_while(|lowest| lowest.is_none())
-        .next()
-        .map(|lowest| lowest.unwrap())
-        .unwrap_or(0)
+    }
 }
 
-fn part1(input: &Input) -> u64 {
-    ideal_quantum_entanglement(input, 3)
+// Tries every first group of increasing size, the way the puzzle was first solved. Only feasible
+// for a few dozen packages, kept to check the search against.
+fn ideal_quantum_entanglement_enumerated(packages: &Input, n_groups: u32) -> Result<Balance, BalanceError> {
+    let per_group = per_group(packages, n_groups)?;
+    let indexes: Vec<u32> = (0..packages.len() as u32).collect();
+    let max_packages = packages.len() - (n_groups as usize - 1);
+
+    for n in 1..=max_packages {
+        let mut firsts: Vec<(BigUint, Vec<usize>)> = Combinations::new(&indexes, n)
+            .map(|first| first.iter().map(|i| *i as usize).collect::<Vec<_>>())
+            .filter(|first| first.iter().map(|i| packages[*i]).sum::<u32>() == per_group)
+            .map(|first| (quantum_entanglement(&first.iter().map(|i| packages[*i]).collect::<Vec<_>>()), first))
+            .collect();
+        firsts.sort();
+
+        for (quantum_entanglement, first) in firsts {
+            let rest: Vec<usize> = (0..packages.len()).filter(|i| !first.contains(i)).collect();
+            if let Some(others) = split(packages, &rest, n_groups as usize - 1, per_group) {
+                let mut groups = vec![first];
+                groups.extend(others);
+                return Ok(Balance { groups, quantum_entanglement });
+            }
+        }
+    }
+
+    Err(BalanceError::Infeasible { groups: n_groups, per_group })
 }
 
-fn part2(input: &Input) -> u64 {
-    ideal_quantum_entanglement(input, 4)
+// Splits the packages at the given indexes into n_groups groups weighing per_group each
+fn split(packages: &[u32], indexes: &[usize], n_groups: usize, per_group: u32) -> Option<Vec<Vec<usize>>> {
+    let mut sorted = indexes.to_vec();
+    sorted.sort_by_key(|i| cmp::Reverse(packages[*i]));
+    Split { packages, per_group: per_group as usize, unsplittable: HashSet::new() }.groups(&sorted, n_groups)
+}
+
+// The sums up to max that the packages at each suffix of the indexes can add up to, as bit sets
+fn suffix_sums(packages: &[u32], indexes: &[usize], max: usize) -> Vec<Vec<u64>> {
+    let words = max / 64 + 1;
+    let mut sums = vec![vec![0u64; words]; indexes.len() + 1];
+    sums[indexes.len()][0] = 1;
+    for j in (0..indexes.len()).rev() {
+        let (head, tail) = sums.split_at_mut(j + 1);
+        let next = &tail[0];
+        let w = packages[indexes[j]] as usize;
+        let (shift_words, shift_bits) = (w / 64, w % 64);
+        for (k, bits) in head[j].iter_mut().enumerate() {
+            *bits = next[k];
+            if k >= shift_words {
+                *bits |= next[k - shift_words] << shift_bits;
+                if shift_bits > 0 && k > shift_words {
+                    *bits |= next[k - shift_words - 1] >> (64 - shift_bits);
+                }
+            }
+        }
+    }
+    sums
+}
+
+fn reachable(sums: &[Vec<u64>], j: usize, sum: usize) -> bool {
+    (sums[j][sum / 64] >> (sum % 64)) & 1 == 1
+}
+
+// Every group is alike so one of them holds the heaviest package. That group is filled with the
+// packages the suffix sums say can still be completed to the group weight, so every group tried
+// is a real one and with two groups the first one tried is an answer. With more groups the rest
+// is split the same way for every such group until one works, and sets of packages that can't be
+// split are remembered.
+struct Split<'a> {
+    packages: &'a [u32],
+    per_group: usize,
+    unsplittable: HashSet<Vec<usize>>
+}
+
+impl<'a> Split<'a> {
+    // Splits the packages at the given indexes, heaviest first, into n_groups groups
+    fn groups(&mut self, indexes: &[usize], n_groups: usize) -> Option<Vec<Vec<usize>>> {
+        let total: usize = indexes.iter().map(|i| self.packages[*i] as usize).sum();
+        if total != n_groups * self.per_group {
+            return None;
+        }
+        match n_groups {
+            0 => return if indexes.is_empty() { Some(vec![]) } else { None },
+            1 => return Some(vec![indexes.to_vec()]),
+            _ => {}
+        }
+        if self.unsplittable.contains(indexes) {
+            return None;
+        }
+
+        let sums = suffix_sums(self.packages, indexes, self.per_group);
+        let heaviest = self.packages[indexes[0]] as usize;
+        let groups = if heaviest <= self.per_group && reachable(&sums, 1, self.per_group - heaviest) {
+            self.fill(indexes, &sums, 1, self.per_group - heaviest, &mut vec![indexes[0]], n_groups)
+        } else {
+            None
+        };
+        if groups.is_none() {
+            self.unsplittable.insert(indexes.to_vec());
+        }
+        groups
+    }
+
+    // Adds packages from position j on until the group is remaining heavier, the suffix sums
+    // have to say that's possible
+    fn fill(&mut self, indexes: &[usize], sums: &[Vec<u64>], j: usize, remaining: usize, group: &mut Vec<usize>, n_groups: usize) -> Option<Vec<Vec<usize>>> {
+        if remaining == 0 {
+            let rest: Vec<usize> = indexes.iter().filter(|i| !group.contains(i)).cloned().collect();
+            return self.groups(&rest, n_groups - 1).map(|others| {
+                let mut groups = vec![group.clone()];
+                groups.extend(others);
+                groups
+            });
+        }
+
+        let w = self.packages[indexes[j]] as usize;
+        if w <= remaining && reachable(sums, j + 1, remaining - w) {
+            group.push(indexes[j]);
+            if let Some(groups) = self.fill(indexes, sums, j + 1, remaining - w, group, n_groups) {
+                return Some(groups);
+            }
+            group.pop();
+        }
+        // Leaving out a package and taking one just as heavy after it gives the same groups again
+        let mut next = j + 1;
+        while next < indexes.len() && self.packages[indexes[next]] as usize == w {
+            next += 1;
+        }
+        if reachable(sums, next, remaining) {
+            self.fill(indexes, sums, next, remaining, group, n_groups)
+        } else {
+            None
+        }
+    }
+}
+
+fn part1(input: &Input, reference: bool) -> Result<Balance, BalanceError> {
+    if reference {
+        ideal_quantum_entanglement_enumerated(input, 3)
+    } else {
+        ideal_quantum_entanglement(input, 3)
+    }
+}
+
+fn part2(input: &Input, reference: bool) -> Result<Balance, BalanceError> {
+    if reference {
+        ideal_quantum_entanglement_enumerated(input, 4)
+    } else {
+        ideal_quantum_entanglement(input, 4)
+    }
+}
+
+fn format_result(part: &str, result: Result<Balance, BalanceError>) -> String {
+    match result {
+        Ok(balance) => {
+            let groups: Vec<_> = balance.groups.iter()
+                .map(|group| group.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(" "))
+                .collect();
+            format!("{}: {} (packages {})", part, balance.quantum_entanglement, groups.join(" | "))
+        },
+        Err(e) => format!("{}: {}", part, e)
+    }
+}
+
+struct Options {
+    input: String,
+    reference: bool
+}
+
+impl Options {
+    fn parse<I: Iterator<Item=String>>(args: I) -> Options {
+        let mut input = None;
+        let mut reference = false;
+        for arg in args {
+            match arg.as_str() {
+                "--reference" => reference = true,
+                _ => input = Some(arg)
+            }
+        }
+        Options { input: input.expect("No input file given"), reference }
+    }
 }
 
 fn main() {
     measure(|| {
-        let input = input().expect("Input failed");
+        let options = Options::parse(env::args().skip(1));
+        let input = input(&options.input).expect("Input failed");
+        let reference = options.reference;
 
-        rayon::join(|| println!("Part1: {}", part1(&input)),
-                    || println!("Part2: {}", part2(&input)));
+        rayon::join(|| println!("{}", format_result("Part1", part1(&input, reference))),
+                    || println!("{}", format_result("Part2", part2(&input, reference))));
     });
 }
 
-fn input() -> io::Result<Input> {
-    let f = File::open(env::args().skip(1).next().expect("No input file given"))?;
+fn input(path: &str) -> io::Result<Input> {
+    let f = File::open(path)?;
     let f = BufReader::new(f);
     Ok(f.lines().map(|l| l.unwrap().parse::<u32>().unwrap()).collect())
 }
@@ -111,6 +477,7 @@ fn input() -> io::Result<Input> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::time::{Duration, Instant};
 
     const INPUT: &'static str =
        "1
@@ -154,13 +521,142 @@ mod tests {
         );
     }
 
+    fn weights(packages: &Input, groups: &[Vec<usize>]) -> Vec<Vec<u32>> {
+        groups.iter().map(|group| group.iter().map(|i| packages[*i]).collect()).collect()
+    }
+
     #[test]
     fn test_part1() {
-        assert_eq!(part1(&as_input(INPUT)), 99);
+        assert_eq!(part1(&as_input(INPUT), false).map(|b| b.quantum_entanglement), Ok(BigUint::from(99)));
+        assert_eq!(part1(&as_input(INPUT), true).map(|b| b.quantum_entanglement), Ok(BigUint::from(99)));
     }
 
     #[test]
     fn test_part2() {
-        assert_eq!(part2(&as_input(INPUT)), 44);
+        assert_eq!(part2(&as_input(INPUT), false).map(|b| b.quantum_entanglement), Ok(BigUint::from(44)));
+        assert_eq!(part2(&as_input(INPUT), true).map(|b| b.quantum_entanglement), Ok(BigUint::from(44)));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_groups() {
+        let input = as_input(INPUT);
+        let balance = part2(&input, false).unwrap();
+        assert_eq!(balance.groups[0], vec![3, 9]);
+        assert_eq!(balance.groups.len(), 4);
+        assert!(weights(&input, &balance.groups).iter().all(|group| group.iter().sum::<u32>() == 15));
+        let mut all: Vec<usize> = balance.groups.concat();
+        all.sort();
+        assert_eq!(all, (0..input.len()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_remaining_must_split() {
+        // 17 1 9 is the smallest first group with the lowest quantum entanglement but
+        // 3 4 7 11 14 15 can't be split into two groups of 27
+        let packages = vec![1, 3, 4, 7, 9, 11, 14, 15, 17];
+        for reference in [false, true] {
+            let balance = part1(&packages, reference).unwrap();
+            assert_eq!(balance.quantum_entanglement, BigUint::from(165));
+            assert_eq!(weights(&packages, &balance.groups), vec![vec![1, 11, 15], vec![17, 7, 3], vec![14, 9, 4]]);
+        }
+    }
+
+    #[test]
+    fn test_matches_enumerated() {
+        let mut rng = Lcg::new(24);
+        for n in 6..16 {
+            for n_groups in 2..5 {
+                let packages: Input = (0..n).map(|_| rng.between(1, 20) as u32).collect();
+                let expected = ideal_quantum_entanglement_enumerated(&packages, n_groups);
+                let found = ideal_quantum_entanglement(&packages, n_groups);
+                assert_eq!(found.as_ref().map(|b| (b.groups[0].len(), &b.quantum_entanglement)),
+                           expected.as_ref().map(|b| (b.groups[0].len(), &b.quantum_entanglement)),
+                           "{:?} in {} groups", packages, n_groups);
+            }
+        }
+    }
+
+    fn check_balance(packages: &Input, n_groups: u32, balance: &Balance) {
+        let per_group = packages.iter().sum::<u32>() / n_groups;
+        assert_eq!(balance.groups.len(), n_groups as usize);
+        let weights = weights(packages, &balance.groups);
+        assert!(weights.iter().all(|group| group.iter().sum::<u32>() == per_group));
+        assert_eq!(quantum_entanglement(&weights[0]), balance.quantum_entanglement);
+        let mut all: Vec<usize> = balance.groups.concat();
+        all.sort();
+        assert_eq!(all, (0..packages.len()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_hundreds_of_packages() {
+        // Groups of 475 are 7t + 5s and the only way with 69 packages or fewer is t = 65, s = 4.
+        // The remaining 135 sevens and 96 fives make three groups of 45 sevens and 32 fives.
+        let mut packages = vec![5; 100];
+        packages.extend(vec![7; 200]);
+        let balance = ideal_quantum_entanglement(&packages, 4).unwrap();
+        check_balance(&packages, 4, &balance);
+        assert_eq!(balance.groups[0].len(), 69);
+        // 7^65 * 5^4, far beyond u128
+        assert_eq!(balance.quantum_entanglement.to_string(), "5336452133406781188106260648713001715805816911824168004375");
+    }
+
+    // n packages of different weights from 1 to 1000 that weigh a multiple of 12 in total
+    fn distinct_weights(rng: &mut Lcg, n: usize) -> Input {
+        let mut packages: Input = vec![];
+        while packages.len() < n || packages.iter().sum::<u32>() % 12 != 0 {
+            let w = rng.between(1, 1000) as u32;
+            if !packages.contains(&w) {
+                if packages.len() == n {
+                    packages.pop();
+                }
+                packages.push(w);
+            }
+        }
+        packages
+    }
+
+    #[test]
+    fn test_split_distinct_weights() {
+        let packages = distinct_weights(&mut Lcg::new(39), 100);
+        let indexes: Vec<usize> = (0..packages.len()).collect();
+        for n_groups in 2..5 {
+            let per_group = packages.iter().sum::<u32>() / n_groups;
+            let groups = split(&packages, &indexes, n_groups as usize, per_group).unwrap();
+            assert!(weights(&packages, &groups).iter().all(|group| group.iter().sum::<u32>() == per_group));
+            let mut all: Vec<usize> = groups.concat();
+            all.sort();
+            assert_eq!(all, indexes);
+        }
+        assert_eq!(split(&[1, 4, 11, 13, 18, 19], &[0, 1, 2, 3, 4, 5], 3, 22), None);
+        assert_eq!(split(&[3, 3, 2, 2, 2], &[0, 1, 2, 3, 4], 2, 6), Some(vec![vec![0, 1], vec![2, 3, 4]]));
+    }
+
+    #[test]
+    fn test_hundreds_of_distinct_weights() {
+        let packages = distinct_weights(&mut Lcg::new(40), 300);
+        let start = Instant::now();
+        for n_groups in 3..5 {
+            let balance = ideal_quantum_entanglement(&packages, n_groups).unwrap();
+            check_balance(&packages, n_groups, &balance);
+            // Different weights can make up any total between the lightest and the heaviest few
+            let mut heaviest = packages.clone();
+            heaviest.sort_by_key(|w| cmp::Reverse(*w));
+            let per_group = packages.iter().sum::<u32>() / n_groups;
+            let fewest = (1..).find(|n| heaviest[..*n].iter().sum::<u32>() >= per_group).unwrap();
+            assert_eq!(balance.groups[0].len(), fewest);
+        }
+        assert!(start.elapsed() < Duration::from_secs(30), "took {:?}", start.elapsed());
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(ideal_quantum_entanglement(&vec![1, 2, 3], 0).unwrap_err(), BalanceError::NoGroups);
+        assert_eq!(part1(&vec![3, 3], false).unwrap_err(), BalanceError::TooFewPackages { packages: 2, groups: 3 });
+        assert_eq!(part1(&vec![1, 2, 3, 4], false).unwrap_err(), BalanceError::UnevenTotal { total: 10, groups: 3 });
+        assert_eq!(part1(&vec![u32::MAX, 1, 2], false).unwrap_err(), BalanceError::TooHeavy);
+        assert_eq!(part1(&vec![1, 4, 11, 13, 18, 19], false).unwrap_err(), BalanceError::Infeasible { groups: 3, per_group: 22 });
+        assert_eq!(part1(&vec![1, 4, 11, 13, 18, 19], true).unwrap_err(), BalanceError::Infeasible { groups: 3, per_group: 22 });
+        assert_eq!(part1(&vec![1, 4, 11, 13, 18, 19], false).unwrap_err().to_string(), "the packages can't be split into 3 groups weighing 22 each");
+        assert_eq!(ideal_quantum_entanglement(&vec![5, 2, 3], 1).map(|b| b.quantum_entanglement), Ok(BigUint::from(30)));
+    }
+}
@@ -1,7 +1,11 @@
 extern crate time;
 
+mod big_uint;
+
 use time::*;
 
+pub use big_uint::BigUint;
+
 pub fn measure<F>(f: F) where F: FnOnce() -> () {
     let start = precise_time_ns();
 
@@ -21,3 +25,19 @@ pub fn measure_times<F>(times: usize, f: F) where F: Fn() -> () {
     let dur_ns = precise_time_ns() - start;
     println!("It took: {}ms on average for {} times", (dur_ns / times as u64) as f64 / 1_000_000.0, times);
 }
+
+// A small deterministic pseudo random number generator, for tests that check a solution against
+// brute force on varied inputs without depending on the rand crate
+pub struct Lcg(u64);
+
+impl Lcg {
+    pub fn new(seed: u64) -> Lcg {
+        Lcg(seed)
+    }
+
+    // A number from low up to and including high
+    pub fn between(&mut self, low: i64, high: i64) -> i64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        low + ((self.0 >> 33) % (high - low + 1) as u64) as i64
+    }
+}